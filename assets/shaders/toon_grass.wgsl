@@ -0,0 +1,18 @@
+// A fragment shader used by the grass_material example.
+// The vertex shader is the built-in grass shader, so the input is its output
+#import warbler_grass::types::VertexOutput
+
+// The bind group of the material is always bound at group 7
+@group(7) @binding(0)
+var<uniform> tint: vec4<f32>;
+@group(7) @binding(1)
+var<uniform> bands: f32;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // quantize the brightness of the blade into a few flat bands
+    let brightness = max(max(in.color.r, in.color.g), in.color.b);
+    let band = ceil(brightness * bands) / bands;
+    let color = in.color.rgb / max(brightness, 0.0001) * band;
+    return vec4<f32>(color * tint.rgb, 1.0);
+}
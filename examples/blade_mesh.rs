@@ -0,0 +1,75 @@
+//! Shows how to generate blade meshes instead of modeling them.
+//! The left chunk uses curved double sided blades, the right one leaning blades made of a triangle fan.
+//! Both chunks use simpler meshes for blades far away from the camera
+use bevy::{prelude::*, render::primitives::Aabb};
+use warbler_grass::{
+    blade_mesh::{BladeStyle, GrassBladeMeshBuilder},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .run();
+}
+
+fn setup_grass(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+) {
+    // The generated meshes have normals, so the blades are lit by directional lights
+    commands.spawn(DirectionalLightBundle {
+        transform: Transform::from_xyz(1., 2., 1.).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+    let curved = GrassBladeMeshBuilder::new()
+        .with_segments(5)
+        .with_width(0.15, 0.)
+        .with_curvature(0.5);
+    let leaning = GrassBladeMeshBuilder::new()
+        .with_style(BladeStyle::TriangleFan)
+        .with_width(0.2, 0.05)
+        .with_curvature(0.)
+        .with_lean(0.4);
+
+    for (x, builder) in [(0., curved), (50., leaning)] {
+        // Blades far away from the camera are drawn with fewer segments
+        let mut lods = builder
+            .build_lods(3)
+            .into_iter()
+            .map(|mesh| meshes.add(mesh));
+        let grass_mesh = lods.next().unwrap();
+        let lods = lods
+            .zip([20., 40.])
+            .fold(GrassLods::default(), |lods, (mesh, distance)| {
+                lods.with_level(mesh, distance)
+            });
+        commands.spawn((
+            WarblersBundle {
+                grass_mesh,
+                density_map: DensityMap {
+                    density_map: asset_server.load("grass_density_map.png"),
+                    density: 4.,
+                },
+                y_map: YMap {
+                    y_map: asset_server.load("grass_y_map.png"),
+                },
+                height: WarblerHeight::Uniform(1.5),
+                aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(50., 10., 50.)),
+                spatial: SpatialBundle::from_transform(Transform::from_xyz(x, 0., 0.)),
+                ..default()
+            },
+            lods,
+        ));
+    }
+}
@@ -0,0 +1,25 @@
+//! Shows how to spawn a chunk from a `.grass.ron` file.
+//!
+//! The file references the maps of the chunk and stores its parameters.
+//! With the `file_watcher` feature of bevy, changes to the file or the maps are applied while the game is running
+use bevy::prelude::*;
+use warbler_grass::{chunk_asset::GrassChunkBundle, prelude::*};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .run();
+}
+fn setup_grass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // The components of the chunk are inserted once the file and its maps are loaded
+    commands.spawn(GrassChunkBundle::new(asset_server.load("meadow.grass.ron")));
+}
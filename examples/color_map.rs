@@ -0,0 +1,84 @@
+//! Shows how to vary the color of a chunk using a color map.
+//! The color map is generated here, but it is usually painted alongside the density map
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use warbler_grass::prelude::*;
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .run();
+}
+
+fn setup_grass(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    let y_map = YMap {
+        y_map: asset_server.load("grass_y_map.png"),
+    };
+    let density_map = DensityMap {
+        density_map: asset_server.load("grass_density_map.png"),
+        density: 2.,
+    };
+    // The color map is stretched over the aabb, just like the y-map and density map
+    let color_map = ColorMap::new(images.add(color_map_image()));
+    commands.spawn((
+        WarblersBundle {
+            density_map,
+            y_map,
+            aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+            ..default()
+        },
+        color_map,
+    ));
+}
+
+/// White grass with some dry patches and a reddish path through the middle
+fn color_map_image() -> Image {
+    const SIZE: u32 = 128;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (u, v) = (x as f32 / SIZE as f32, y as f32 / SIZE as f32);
+            let path = (v - 0.5 - (u * 8.).sin() * 0.1).abs() < 0.04;
+            let dry = (u * 13.).sin() * (v * 9.).cos() > 0.6;
+            // The colors are multiplied with the grass color, white keeps it unchanged
+            let pixel = if path {
+                [255, 120, 80, 255]
+            } else if dry {
+                [255, 200, 40, 255]
+            } else {
+                [255, 255, 255, 255]
+            };
+            data.extend(pixel);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
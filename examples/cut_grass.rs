@@ -0,0 +1,62 @@
+//! Shows how to cut and flatten grass at runtime.
+//!
+//! Press `C` to mow the grass in front of the camera and `F` to flatten it.
+//! The grass grows back after a few seconds
+use bevy::{math::Vec3Swizzles, prelude::*, render::primitives::Aabb};
+use warbler_grass::{
+    cutting::{GrassCut, GrassCutMode, GrassShape},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .add_systems(Update, cut_grass)
+        .run();
+}
+fn setup_grass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(WarblersBundle {
+        y_map: YMap::new(asset_server.load("grass_y_map.png")),
+        density_map: DensityMap::new(asset_server.load("grass_density_map.png"), 2.),
+        height: WarblerHeight::Uniform(2.),
+        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 5., 100.)),
+        ..default()
+    });
+}
+fn cut_grass(
+    input: Res<ButtonInput<KeyCode>>,
+    camera: Query<&Transform, With<Camera>>,
+    mut cuts: EventWriter<GrassCut>,
+) {
+    let mode = if input.pressed(KeyCode::KeyC) {
+        GrassCutMode::Remove
+    } else if input.pressed(KeyCode::KeyF) {
+        GrassCutMode::Flatten
+    } else {
+        return;
+    };
+    let Ok(transform) = camera.get_single() else {
+        return;
+    };
+    // a stripe in front of the camera
+    let start = transform.translation.xz();
+    let end = start + transform.forward().xz().normalize_or_zero() * 30.;
+    cuts.send(GrassCut {
+        shape: GrassShape::Capsule {
+            start,
+            end,
+            radius: 3.,
+        },
+        mode,
+        regrowth: Some(5.),
+    });
+}
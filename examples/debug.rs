@@ -0,0 +1,61 @@
+//! Shows how to inspect grass chunks with the `WarblerDebugPlugin`.
+//!
+//! The aabbs of the chunks are colored by their status:
+//! yellow while the density map is dithered, green when the chunk is ready and red on errors.
+//! Press 1 to toggle the aabbs, 2 for the density map, 3 for the y-map and 4 for the blade positions
+use bevy::{prelude::*, render::primitives::Aabb};
+use warbler_grass::{
+    debug::{WarblerDebugPlugin, WarblerDebugSettings},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Draws the gizmos of the chunks
+            WarblerDebugPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .add_systems(Update, toggle_overlays)
+        .run();
+}
+fn setup_grass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let y_map = YMap::new(asset_server.load("grass_y_map.png"));
+    let density_map = asset_server.load("grass_density_map.png");
+    commands.spawn(WarblersBundle {
+        y_map: y_map.clone(),
+        density_map: DensityMap::new(density_map.clone(), 1.),
+        height: WarblerHeight::Uniform(2.),
+        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(50., 5., 50.)),
+        ..default()
+    });
+    // A negative density can't be dithered, so this chunk is drawn red
+    commands.spawn(WarblersBundle {
+        y_map,
+        density_map: DensityMap::new(density_map, -1.),
+        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(50., 5., 50.)),
+        spatial: SpatialBundle::from_transform(Transform::from_xyz(60., 0., 0.)),
+        ..default()
+    });
+}
+fn toggle_overlays(input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<WarblerDebugSettings>) {
+    if input.just_pressed(KeyCode::Digit1) {
+        settings.aabbs = !settings.aabbs;
+    }
+    if input.just_pressed(KeyCode::Digit2) {
+        settings.density_map = !settings.density_map;
+    }
+    if input.just_pressed(KeyCode::Digit3) {
+        settings.y_map = !settings.y_map;
+    }
+    if input.just_pressed(KeyCode::Digit4) {
+        settings.blade_positions = !settings.blade_positions;
+    }
+}
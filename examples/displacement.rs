@@ -0,0 +1,66 @@
+//! Shows how objects can push the grass aside.
+//!
+//! A ball rolls over the field and leaves a trail of bent grass behind
+use bevy::{prelude::*, render::primitives::Aabb};
+use warbler_grass::{
+    displacement::{GrassDisplacer, GrassTrail},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_ball)
+        .run();
+}
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands.spawn(WarblersBundle {
+        y_map: YMap::new(asset_server.load("grass_y_map.png")),
+        density_map: DensityMap::new(asset_server.load("grass_density_map.png"), 2.),
+        height: WarblerHeight::Uniform(2.),
+        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 5., 100.)),
+        ..default()
+    });
+    // The trail keeps the grass bent for a few seconds after the ball rolled over it
+    let trail = GrassTrail::new(Rect::new(0., 0., 100., 100.), 2., 0.3, images.as_mut());
+    commands.insert_resource(trail);
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Sphere::new(1.5)),
+            material: materials.add(Color::WHITE),
+            ..default()
+        },
+        // The interesting part of the example
+        GrassDisplacer {
+            radius: 4.,
+            strength: 2.,
+            falloff: 1.5,
+        },
+    ));
+    commands.spawn(DirectionalLightBundle {
+        transform: Transform::from_xyz(1., 2., 1.).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+fn move_ball(time: Res<Time>, mut balls: Query<&mut Transform, With<GrassDisplacer>>) {
+    let t = time.elapsed_seconds() * 0.3;
+    for mut transform in &mut balls {
+        transform.translation = Vec3::new(50. + t.cos() * 30., 3., 50. + (2. * t).sin() * 20.);
+    }
+}
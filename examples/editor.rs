@@ -0,0 +1,134 @@
+//! Shows how to paint the maps of a chunk in game with the `GrassEditorPlugin`.
+//!
+//! Hold the left mouse button to paint.
+//! Press 1 to paint the density map, 2 the y-map, 3 the height and 4 the color.
+//! Tab switches between adding, subtracting and smoothing, the up and down arrows change the size of the brush.
+//! Strokes are undone with Z and redone with Y.
+//! F5 saves the painted chunk into the temp directory, F9 replaces the chunk with the saved one
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use warbler_grass::{
+    editor::{BrushMode, GrassEditor, GrassEditorPlugin, PaintTarget},
+    history::{GrassEditHistory, GrassHistoryCommand},
+    persistence::{load_chunk, save_chunk, MapEncoding},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Paints into the maps of the chunk under the cursor
+            GrassEditorPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .add_systems(Update, (change_brush, undo_strokes, save_and_load))
+        .run();
+}
+fn setup_grass(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    // The painted images have to be kept in the main world
+    let blank = |pixel: &[u8], format| {
+        Image::new_fill(
+            Extent3d {
+                width: 128,
+                height: 128,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixel,
+            format,
+            RenderAssetUsages::default(),
+        )
+    };
+    commands.spawn((
+        WarblersBundle {
+            y_map: YMap::new(asset_server.load("grass_y_map.png")),
+            density_map: DensityMap::new(asset_server.load("grass_density_map.png"), 1.),
+            height: WarblerHeight::Texture(images.add(blank(&[127], TextureFormat::R8Unorm))),
+            aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+            ..default()
+        },
+        ColorMap::new(images.add(blank(&[255, 255, 255, 255], TextureFormat::Rgba8UnormSrgb))),
+    ));
+}
+fn change_brush(input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<GrassEditor>) {
+    if input.just_pressed(KeyCode::Digit1) {
+        editor.target = PaintTarget::Density;
+    }
+    if input.just_pressed(KeyCode::Digit2) {
+        editor.target = PaintTarget::YMap;
+    }
+    if input.just_pressed(KeyCode::Digit3) {
+        editor.target = PaintTarget::Height;
+    }
+    if input.just_pressed(KeyCode::Digit4) {
+        editor.target = PaintTarget::Color(Color::rgb(1., 0.6, 0.2));
+    }
+    if input.just_pressed(KeyCode::Tab) {
+        editor.brush.mode = match editor.brush.mode {
+            BrushMode::Add => BrushMode::Subtract,
+            BrushMode::Subtract => BrushMode::Smooth,
+            BrushMode::Smooth => BrushMode::Add,
+        };
+    }
+    if input.pressed(KeyCode::ArrowUp) {
+        editor.brush.radius += 0.2;
+    }
+    if input.pressed(KeyCode::ArrowDown) {
+        editor.brush.radius = (editor.brush.radius - 0.2).max(0.5);
+    }
+}
+fn undo_strokes(input: Res<ButtonInput<KeyCode>>, mut history: EventWriter<GrassHistoryCommand>) {
+    if input.just_pressed(KeyCode::KeyZ) {
+        history.send(GrassHistoryCommand::Undo);
+    }
+    if input.just_pressed(KeyCode::KeyY) {
+        history.send(GrassHistoryCommand::Redo);
+    }
+}
+fn save_and_load(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    chunk: Query<Entity, With<DensityMap>>,
+) {
+    let Ok(chunk) = chunk.get_single() else {
+        return;
+    };
+    let directory = std::env::temp_dir().join("warbler_grass_editor");
+    if input.just_pressed(KeyCode::F5) {
+        commands.add(move |world: &mut World| {
+            match save_chunk(world, chunk, &directory, MapEncoding::Png16) {
+                Ok(_) => info!("Saved the grass to {}", directory.display()),
+                Err(error) => error!("Couldn't save the grass: {error}"),
+            }
+        });
+    } else if input.just_pressed(KeyCode::F9) {
+        commands.add(
+            move |world: &mut World| match load_chunk(&directory, world) {
+                Ok(loaded) => {
+                    world.despawn(chunk);
+                    // the edits refer to the images of the despawned chunk
+                    world.resource_mut::<GrassEditHistory>().clear();
+                    loaded.spawn(world);
+                }
+                Err(error) => error!("Couldn't load the grass: {error}"),
+            },
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_resource::{AsBindGroup, ShaderRef},
+    },
+};
+use warbler_grass::{
+    material::{GrassMaterial, GrassMaterialPlugin},
+    prelude::*,
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Adds the render pipeline for our own material
+            GrassMaterialPlugin::<ToonGrassMaterial>::default(),
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, setup_grass)
+        .run();
+}
+
+/// A material drawing the grass in a few flat color bands
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct ToonGrassMaterial {
+    #[uniform(0)]
+    tint: Color,
+    #[uniform(1)]
+    bands: f32,
+}
+impl GrassMaterial for ToonGrassMaterial {
+    // the vertex shader of the built-in grass shader is used, since we don't override it
+    fn fragment_shader() -> ShaderRef {
+        "shaders/toon_grass.wgsl".into()
+    }
+}
+
+fn setup_grass(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ToonGrassMaterial>>,
+) {
+    let y_map = YMap {
+        y_map: asset_server.load("grass_y_map.png"),
+    };
+    let density_map = DensityMap {
+        density_map: asset_server.load("grass_density_map.png"),
+        density: 2.,
+    };
+    let material = materials.add(ToonGrassMaterial {
+        tint: Color::rgb(1., 0.95, 0.8),
+        bands: 3.,
+    });
+    // The material is added as an additional component to the chunk
+    commands.spawn((
+        WarblersBundle {
+            density_map,
+            y_map,
+            aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+            ..default()
+        },
+        material,
+    ));
+}
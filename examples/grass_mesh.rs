@@ -27,11 +27,24 @@ fn setup_grass(
 ) {
     // The interesting part in this example
     // We could use any mesh we want. We should choose a low poly mesh however since we draw a lot of them
-    let grass_mesh: Handle<Mesh> = meshes.add(Capsule3d {
+    let mut capsule = Mesh::from(Capsule3d {
         radius: 0.3,
         half_length: 1.,
-        ..default()
     });
+    // Vertex colors and normals of the mesh are used as well
+    // Here we darken the capsules towards the ground
+    let colors: Vec<[f32; 4]> = capsule
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+        .unwrap_or_default()
+        .iter()
+        .map(|[_, y, _]| {
+            let brightness = 0.3 + 0.7 * ((y + 1.3) / 2.6).clamp(0., 1.);
+            [brightness, brightness, brightness, 1.]
+        })
+        .collect();
+    capsule.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    let grass_mesh: Handle<Mesh> = meshes.add(capsule);
     // we use a resource to keep track of the handles
     // so we can swap them later
     let store = GrassMeshStore {
@@ -50,13 +63,17 @@ fn setup_grass(
         density: 1.,
     };
     // simple add the grass mesh in the bundle, instead of using the default
-    commands.spawn(WarblersBundle {
-        grass_mesh,
-        density_map,
-        y_map,
-        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
-        ..default()
-    });
+    commands.spawn((
+        WarblersBundle {
+            grass_mesh,
+            density_map,
+            y_map,
+            aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+            ..default()
+        },
+        // lights the capsules with their normals
+        GrassLighting,
+    ));
 }
 
 /// Used to keep track of the standard mesh and the custom mesh for the grass
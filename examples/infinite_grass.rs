@@ -0,0 +1,40 @@
+//! Shows how to generate grass procedurally around the camera.
+//!
+//! No density maps or y-maps are loaded. Instead, the maps are generated from noise
+//! and the tiles are recycled while you move around.
+use bevy::prelude::*;
+use warbler_grass::{
+    prelude::*,
+    procedural::{NoiseGenerator, ProceduralGrass, ProceduralGrassAnchor, ProceduralGrassPlugin},
+};
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Spawns the grass tiles around the anchor
+            ProceduralGrassPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .insert_resource(ProceduralGrass {
+            max_y: 8.,
+            ..ProceduralGrass::new(NoiseGenerator {
+                y_amplitude: 8.,
+                ..default()
+            })
+        })
+        .add_systems(Update, anchor_camera)
+        .run();
+}
+
+// The grass should follow the camera spawned by the helper
+fn anchor_camera(mut commands: Commands, cameras: Query<Entity, Added<Camera>>) {
+    for camera in &cameras {
+        commands.entity(camera).insert(ProceduralGrassAnchor);
+    }
+}
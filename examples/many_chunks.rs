@@ -1,6 +1,7 @@
 //! Demonstrates how to spawn multiple chunks
 //!
-//! Currently, the example spawns around 3 million grass blades in total
+//! Currently, the example spawns around 3 million grass blades in total.
+//! Distant chunks fade into the fog of the camera
 use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*, render::primitives::Aabb};
 use warbler_grass::{diagnostic::WarblerDiagnosticsPlugin, prelude::*};
 mod helper;
@@ -18,6 +19,7 @@ fn main() {
             LogDiagnosticsPlugin::default(),
         ))
         .add_systems(Startup, setup_grass_chunks)
+        .add_systems(Update, add_fog)
         .run();
 }
 fn setup_grass_chunks(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -66,3 +68,17 @@ fn setup_grass_chunks(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
     }
 }
+
+// The grass uses the fog settings of the camera, just like the `StandardMaterial`
+fn add_fog(mut commands: Commands, cameras: Query<Entity, Added<Camera3d>>) {
+    for camera in &cameras {
+        commands.entity(camera).insert(FogSettings {
+            color: Color::rgb(0.6, 0.7, 0.8),
+            falloff: FogFalloff::Linear {
+                start: 100.,
+                end: 400.,
+            },
+            ..default()
+        });
+    }
+}
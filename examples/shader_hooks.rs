@@ -0,0 +1,67 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+use warbler_grass::{
+    prelude::*,
+    shader_hooks::{GrassShaderHooks, GrassShaderStage},
+};
+mod helper;
+
+// Replaces the color stage of the grass shader.
+// The built-in modules can be imported, here the color uniform of the chunk is used
+const STRIPED_COLOR: &str = "
+#import warbler_grass::bindings::{color as color_uniform}
+
+fn color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32> {
+    let lambda = clamp(vertex_position.y, 0., 1.);
+    let stripe = step(0.5, fract(field_position.x / 8.));
+    let main_color = mix(color_uniform.main_color, color_uniform.main_color * 1.4, stripe);
+    return mix(color_uniform.bottom_color, main_color, lambda);
+}
+";
+// Replaces the wind stage with a simple wave
+const WAVE_WIND: &str = "
+#import warbler_grass::bindings::config
+
+fn wind(world_root: vec3<f32>, field_position: vec2<f32>, local_wind: vec2<f32>) -> vec2<f32> {
+    let wave = sin(dot(world_root.xz, vec2<f32>(0.2, 0.1)) + config.noise_offset.x * 0.05);
+    return local_wind * wave * 0.5;
+}
+";
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        .add_systems(Startup, (setup_grass, setup_hooks))
+        .add_systems(Update, toggle_wind_hook)
+        .run();
+}
+fn setup_hooks(mut hooks: ResMut<GrassShaderHooks>) {
+    hooks.set(GrassShaderStage::Color, STRIPED_COLOR);
+    hooks.set(GrassShaderStage::Wind, WAVE_WIND);
+}
+// Pressing T switches between the built-in wind and the wave
+fn toggle_wind_hook(input: Res<ButtonInput<KeyCode>>, mut hooks: ResMut<GrassShaderHooks>) {
+    if input.just_pressed(KeyCode::KeyT) && hooks.remove(GrassShaderStage::Wind).is_none() {
+        hooks.set(GrassShaderStage::Wind, WAVE_WIND);
+    }
+}
+fn setup_grass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let y_map = YMap {
+        y_map: asset_server.load("grass_y_map.png"),
+    };
+    let density_map = DensityMap {
+        density_map: asset_server.load("grass_density_map.png"),
+        density: 2.,
+    };
+    commands.spawn(WarblersBundle {
+        density_map,
+        y_map,
+        aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+        ..default()
+    });
+}
@@ -22,9 +22,12 @@ fn main() {
         ))
         // creates our grass
         .add_systems(Startup, setup_grass)
-        // more wind
+        // more wind, with some gusts
         .insert_resource(GrassConfiguration {
             wind: Vec2::new(2., 2.),
+            wind_speed: 140.,
+            gust_amplitude: 0.5,
+            ..default()
         })
         .run();
 }
@@ -0,0 +1,151 @@
+//! Shows how to draw textured grass cards instead of plain blades.
+//! The mesh needs uvs and the chunk a `GrassAlbedo` component.
+//! You can press B to turn the cards towards the camera
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use warbler_grass::prelude::*;
+mod helper;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            // This plugin is needed to initialize everything for the grass render pipeline
+            WarblersPlugin,
+            // Just a helper plugin for spawning a camera
+            // As in all examples, you can use the wasd keys for movement and qe for rotation
+            helper::SimpleCamera,
+        ))
+        // MSAA smooths the edges of the cutout using alpha to coverage
+        .insert_resource(Msaa::Sample4)
+        .add_systems(Startup, setup_grass)
+        .add_systems(Update, toggle_billboard)
+        .run();
+}
+
+fn setup_grass(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    let y_map = YMap {
+        y_map: asset_server.load("grass_y_map.png"),
+    };
+    let density_map = DensityMap {
+        density_map: asset_server.load("grass_density_map.png"),
+        density: 1.,
+    };
+    // The albedo is multiplied with the grass color, so we keep the colors bright
+    let color = GrassColor {
+        main_color: Color::WHITE,
+        bottom_color: Color::rgb(0.6, 0.6, 0.6),
+    };
+    commands.spawn((
+        WarblersBundle {
+            grass_mesh: meshes.add(cross_card_mesh()),
+            density_map,
+            y_map,
+            grass_color: color,
+            aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(100., 10., 100.)),
+            ..default()
+        },
+        // Everything with an alpha below the cutoff is discarded
+        GrassAlbedo::new(images.add(flower_texture())),
+    ));
+}
+
+/// Two crossing quads with uvs, so the card is visible from every side
+fn cross_card_mesh() -> Mesh {
+    let (w, h) = (0.4, 1.);
+    let positions = vec![
+        [-w, 0., 0.],
+        [w, 0., 0.],
+        [w, h, 0.],
+        [-w, h, 0.],
+        [0., 0., -w],
+        [0., 0., w],
+        [0., h, w],
+        [0., h, -w],
+    ];
+    let uvs = vec![
+        [0., 1.],
+        [1., 1.],
+        [1., 0.],
+        [0., 0.],
+        [0., 1.],
+        [1., 1.],
+        [1., 0.],
+        [0., 0.],
+    ];
+    let mut indices = Vec::new();
+    for quad in [0, 4] {
+        // both windings, since back faces are culled
+        indices.extend([quad, quad + 1, quad + 2, quad, quad + 2, quad + 3]);
+        indices.extend([quad, quad + 2, quad + 1, quad, quad + 3, quad + 2]);
+    }
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// A simple flower on a stem, surrounded by transparent pixels
+fn flower_texture() -> Image {
+    const SIZE: u32 = 64;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (u, v) = (x as f32 / SIZE as f32, y as f32 / SIZE as f32);
+            let blossom = Vec2::new(u - 0.5, v - 0.25).length() < 0.2;
+            let stem = (u - 0.5).abs() < 0.04 && v > 0.3;
+            let pixel = if blossom {
+                [255, 120, 200, 255]
+            } else if stem {
+                [60, 160, 40, 255]
+            } else {
+                [0, 0, 0, 0]
+            };
+            data.extend(pixel);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// Adds or removes the `GrassBillboard` component, which turns the cards towards the camera
+fn toggle_billboard(
+    mut commands: Commands,
+    chunks: Query<(Entity, Has<GrassBillboard>), With<GrassAlbedo>>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if !input.just_pressed(KeyCode::KeyB) {
+        return;
+    }
+    for (entity, billboard) in &chunks {
+        if billboard {
+            commands.entity(entity).remove::<GrassBillboard>();
+        } else {
+            commands.entity(entity).insert(GrassBillboard);
+        }
+    }
+}
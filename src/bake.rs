@@ -0,0 +1,342 @@
+//! Contains the [`GrassBake`], which stores the dithered blades of a chunk in a `.grassbake` file.
+//!
+//! Dithering large density maps can take seconds each time a level is loaded.
+//! A bake created with [`bake_chunk`] or [`bake_density_map`] skips the dithering:
+//! adding its [`Handle<GrassBake>`] to a chunk uses the baked blades instead.
+//!
+//! The bake stores a hash of the density map, density and size of the chunk it was created from.
+//! If they changed since, the bake is stale and the density map is dithered as usual.
+//! The same happens if the bake can't be loaded
+//! ```no_run
+//! # use bevy::prelude::*;
+//! use warbler_grass::{bake::GrassBake, prelude::*};
+//!
+//! fn spawn_chunk(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn((
+//!         WarblersBundle {
+//!             density_map: DensityMap::new(asset_server.load("grass_density_map.png"), 2.),
+//!             ..default()
+//!         },
+//!         asset_server.load::<GrassBake>("meadow.grassbake"),
+//!     ));
+//! }
+//! ```
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{primitives::Aabb, render_resource::TextureFormat},
+    utils::BoxedFuture,
+};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    dithering::{dither_density_map, DitherCell, DitheredBuffer},
+    map::DensityMap,
+    persistence::GrassFileError,
+};
+
+const MAGIC: &[u8; 4] = b"WGBK";
+/// The version of the file format and the dithering.
+///
+/// Bakes of other versions are rejected
+const VERSION: u16 = 1;
+const COMPRESSED: u16 = 1;
+
+/// Whether a bake is compressed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BakeCompression {
+    None,
+    /// Compresses the blades with zlib.
+    ///
+    /// Bakes are usually a lot smaller, but take longer to load
+    #[default]
+    Zlib,
+}
+
+/// The baked blades of a chunk, loaded from a `.grassbake` file.
+///
+/// Add the handle of the bake to a chunk to use its blades instead of dithering the [`DensityMap`]
+#[derive(Asset, TypePath, Clone)]
+pub struct GrassBake {
+    input_hash: u64,
+    pub(crate) buffer: DitheredBuffer,
+}
+impl GrassBake {
+    /// The hash of the inputs the bake was created from
+    pub fn input_hash(&self) -> u64 {
+        self.input_hash
+    }
+    /// The amount of baked blades
+    pub fn blade_count(&self) -> usize {
+        self.buffer.positions.len()
+    }
+    /// Whether the bake was created from these inputs
+    pub fn matches(&self, density_map: &Image, density: f32, aabb: &Aabb) -> bool {
+        self.input_hash == input_hash(density_map, density, aabb)
+    }
+    fn to_bytes(&self, compression: BakeCompression) -> Result<Vec<u8>, GrassFileError> {
+        let mut blades =
+            Vec::with_capacity(8 + self.buffer.positions.len() * 8 + self.buffer.cells.len() * 16);
+        blades.extend_from_slice(&(self.buffer.positions.len() as u32).to_le_bytes());
+        blades.extend_from_slice(&(self.buffer.cells.len() as u32).to_le_bytes());
+        for position in &self.buffer.positions {
+            blades.extend_from_slice(&position.x.to_le_bytes());
+            blades.extend_from_slice(&position.y.to_le_bytes());
+        }
+        for cell in &self.buffer.cells {
+            blades.extend_from_slice(&cell.center.x.to_le_bytes());
+            blades.extend_from_slice(&cell.center.y.to_le_bytes());
+            blades.extend_from_slice(&cell.instances.start.to_le_bytes());
+            blades.extend_from_slice(&cell.instances.end.to_le_bytes());
+        }
+
+        let mut bytes = Vec::with_capacity(16 + blades.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        let flags = match compression {
+            BakeCompression::None => 0,
+            BakeCompression::Zlib => COMPRESSED,
+        };
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&self.input_hash.to_le_bytes());
+        match compression {
+            BakeCompression::None => bytes.extend_from_slice(&blades),
+            BakeCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+                encoder.write_all(&blades)?;
+                bytes = encoder.finish()?;
+            }
+        }
+        Ok(bytes)
+    }
+    fn from_bytes(bytes: &[u8]) -> Result<GrassBake, GrassFileError> {
+        if bytes.len() < 16 || &bytes[0..4] != MAGIC {
+            return Err(GrassFileError::InvalidBake("not a grass bake"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(GrassFileError::InvalidBake(
+                "the bake was created by another version",
+            ));
+        }
+        let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+        let input_hash = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let mut blades = Vec::new();
+        if flags & COMPRESSED != 0 {
+            ZlibDecoder::new(&bytes[16..]).read_to_end(&mut blades)?;
+        } else {
+            blades.extend_from_slice(&bytes[16..]);
+        }
+
+        let mut values = blades
+            .chunks_exact(4)
+            .map(|value| <[u8; 4]>::try_from(value).unwrap());
+        let mut next = || {
+            values
+                .next()
+                .ok_or(GrassFileError::InvalidBake("the bake is truncated"))
+        };
+        let position_count = u32::from_le_bytes(next()?) as usize;
+        let cell_count = u32::from_le_bytes(next()?) as usize;
+        if blades.len() != 8 + position_count * 8 + cell_count * 16 {
+            return Err(GrassFileError::InvalidBake("the bake is truncated"));
+        }
+        let mut positions = Vec::with_capacity(position_count);
+        for _ in 0..position_count {
+            positions.push(Vec2::new(
+                f32::from_le_bytes(next()?),
+                f32::from_le_bytes(next()?),
+            ));
+        }
+        let mut cells = Vec::with_capacity(cell_count);
+        for _ in 0..cell_count {
+            let center = Vec2::new(f32::from_le_bytes(next()?), f32::from_le_bytes(next()?));
+            let instances = u32::from_le_bytes(next()?)..u32::from_le_bytes(next()?);
+            if instances.start > instances.end || instances.end as usize > position_count {
+                return Err(GrassFileError::InvalidBake(
+                    "a cell points outside of the blades",
+                ));
+            }
+            cells.push(DitherCell { center, instances });
+        }
+        Ok(GrassBake {
+            input_hash,
+            buffer: DitheredBuffer { positions, cells },
+        })
+    }
+}
+
+/// Dithers a density map and encodes the blades as a `.grassbake` file
+pub fn bake_density_map(
+    density_map: &Image,
+    density: f32,
+    aabb: &Aabb,
+    compression: BakeCompression,
+) -> Result<Vec<u8>, GrassFileError> {
+    let field_size = aabb.half_extents.xz() * 2.;
+    let buffer = dither_density_map(density_map.clone(), density, field_size)?;
+    GrassBake {
+        input_hash: input_hash(density_map, density, aabb),
+        buffer,
+    }
+    .to_bytes(compression)
+}
+
+/// Bakes the blades of a chunk into a `.grassbake` file.
+///
+/// The image of the [`DensityMap`] has to be loaded.
+/// The chunk is dithered again, so this can be called before the chunk finished dithering
+pub fn bake_chunk(
+    world: &World,
+    chunk: Entity,
+    path: impl AsRef<Path>,
+    compression: BakeCompression,
+) -> Result<(), GrassFileError> {
+    let entity = world
+        .get_entity(chunk)
+        .ok_or(GrassFileError::NotAChunk(chunk))?;
+    let (Some(density_map), Some(aabb)) = (entity.get::<DensityMap>(), entity.get::<Aabb>()) else {
+        return Err(GrassFileError::NotAChunk(chunk));
+    };
+    let image = world
+        .resource::<Assets<Image>>()
+        .get(&density_map.density_map)
+        .ok_or(GrassFileError::MissingImage("density map"))?;
+    let bytes = bake_density_map(image, density_map.density, aabb, compression)?;
+    if let Some(directory) = path.as_ref().parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Hashes the inputs of the dithering.
+///
+/// Uses FNV-1a, since the hash has to stay the same across builds
+pub(crate) fn input_hash(density_map: &Image, density: f32, aabb: &Aabb) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    let field_size = aabb.half_extents.xz() * 2.;
+    write(&VERSION.to_le_bytes());
+    write(&format_tag(density_map.texture_descriptor.format).to_le_bytes());
+    write(&density_map.width().to_le_bytes());
+    write(&density_map.height().to_le_bytes());
+    write(&density_map.data);
+    write(&density.to_le_bytes());
+    write(&field_size.x.to_le_bytes());
+    write(&field_size.y.to_le_bytes());
+    hash
+}
+
+/// A fixed tag for each format the dithering supports.
+///
+/// The `Debug` output of the format isn't stable across versions of wgpu, so it can't be hashed
+fn format_tag(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::R8Unorm => 1,
+        TextureFormat::Rg8Unorm => 2,
+        TextureFormat::Rgba8UnormSrgb => 3,
+        TextureFormat::Bgra8Unorm => 4,
+        TextureFormat::Bgra8UnormSrgb => 5,
+        // density maps of other formats can't be dithered, so there are no bakes of them
+        _ => 0,
+    }
+}
+
+/// Loads [`GrassBake`]s from `.grassbake` files
+#[derive(Default)]
+pub struct GrassBakeLoader;
+impl AssetLoader for GrassBakeLoader {
+    type Asset = GrassBake;
+    type Settings = ();
+    type Error = GrassFileError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<GrassBake, GrassFileError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            GrassBake::from_bytes(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["grassbake"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::{Vec3, Vec3Swizzles},
+        prelude::Image,
+        render::{
+            primitives::Aabb,
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use super::{bake_density_map, input_hash, BakeCompression, GrassBake};
+    use crate::dithering::dither_density_map;
+
+    #[test]
+    fn bakes_are_read_back() {
+        let density_map = Image::new(
+            Extent3d {
+                width: 16,
+                height: 16,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..=255).collect(),
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::new(20., 2., 10.));
+        let dithered =
+            dither_density_map(density_map.clone(), 2., aabb.half_extents.xz() * 2.).unwrap();
+        for compression in [BakeCompression::None, BakeCompression::Zlib] {
+            let bytes = bake_density_map(&density_map, 2., &aabb, compression).unwrap();
+            let bake = GrassBake::from_bytes(&bytes).unwrap();
+            assert_eq!(bake.buffer, dithered);
+            assert!(bake.matches(&density_map, 2., &aabb));
+            // the bake is stale once an input changes
+            assert!(!bake.matches(&density_map, 3., &aabb));
+            assert!(GrassBake::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+    #[test]
+    fn input_hash_is_stable() {
+        let density_map = Image::new(
+            Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![0, 64, 128, 255],
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::new(4., 1., 4.));
+        // changing this value invalidates all existing bakes
+        assert_eq!(input_hash(&density_map, 1., &aabb), 10768659058559666885);
+    }
+}
@@ -0,0 +1,270 @@
+//! Contains the [`GrassBladeMeshBuilder`], which generates blade meshes from a few parameters.
+//!
+//! The generated meshes can be used as the `grass_mesh` of the [`WarblersBundle`](crate::prelude::WarblersBundle)
+//! ```
+//! # use warbler_grass::blade_mesh::{BladeStyle, GrassBladeMeshBuilder};
+//! let mesh = GrassBladeMeshBuilder::new()
+//!     .with_segments(4)
+//!     .with_curvature(0.3)
+//!     .with_style(BladeStyle::TriangleFan)
+//!     .build();
+//! ```
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+/// How the outline of the blade is triangulated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BladeStyle {
+    /// A strip of quads, one per segment.
+    ///
+    /// The back side has its own vertices, so both sides have correct normals
+    #[default]
+    DoubleSidedQuads,
+    /// A fan of triangles around the root of the blade.
+    ///
+    /// Uses fewer vertices, the back side reuses the vertices of the front side
+    TriangleFan,
+}
+
+/// Generates grass blade meshes.
+///
+/// The blade grows from the origin up to a height of 1, since the height is scaled by the [`WarblerHeight`](crate::prelude::WarblerHeight).
+/// Its width is along the x axis, curvature and lean bend it towards the z axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrassBladeMeshBuilder {
+    /// The amount of segments along the height of the blade. More segments give smoother curves
+    pub segments: u32,
+    /// The width at the root of the blade
+    pub base_width: f32,
+    /// The width at the tip of the blade. A width of zero creates a pointed tip
+    pub tip_width: f32,
+    /// How far the tip bends forward relative to a straight blade
+    pub curvature: f32,
+    /// How far the whole blade tilts forward
+    pub lean: f32,
+    /// How the blade is triangulated
+    pub style: BladeStyle,
+    /// Whether [`Mesh::ATTRIBUTE_UV_0`] is generated.
+    ///
+    /// The u coordinate goes from the left to the right edge, the v coordinate from the tip to the root
+    pub uvs: bool,
+    /// Whether [`Mesh::ATTRIBUTE_NORMAL`] is generated, which enables the lighting of the blades
+    pub normals: bool,
+}
+impl Default for GrassBladeMeshBuilder {
+    fn default() -> Self {
+        GrassBladeMeshBuilder {
+            segments: 3,
+            base_width: 0.1,
+            tip_width: 0.,
+            curvature: 0.2,
+            lean: 0.,
+            style: BladeStyle::DoubleSidedQuads,
+            uvs: true,
+            normals: true,
+        }
+    }
+}
+impl GrassBladeMeshBuilder {
+    /// Creates a new `GrassBladeMeshBuilder` with default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the amount of segments, at least one segment is used
+    pub fn with_segments(mut self, segments: u32) -> Self {
+        self.segments = segments;
+        self
+    }
+    /// Sets the width at the root and the tip of the blade
+    pub fn with_width(mut self, base_width: f32, tip_width: f32) -> Self {
+        self.base_width = base_width;
+        self.tip_width = tip_width;
+        self
+    }
+    /// Sets the curvature of the blade
+    pub fn with_curvature(mut self, curvature: f32) -> Self {
+        self.curvature = curvature;
+        self
+    }
+    /// Sets the lean of the blade
+    pub fn with_lean(mut self, lean: f32) -> Self {
+        self.lean = lean;
+        self
+    }
+    /// Sets the [`BladeStyle`]
+    pub fn with_style(mut self, style: BladeStyle) -> Self {
+        self.style = style;
+        self
+    }
+    /// Sets whether uvs and normals are generated
+    pub fn with_attributes(mut self, uvs: bool, normals: bool) -> Self {
+        self.uvs = uvs;
+        self.normals = normals;
+        self
+    }
+    /// Builds the blade [`Mesh`]
+    pub fn build(&self) -> Mesh {
+        let segments = self.segments.max(1);
+        // the outline of the front side from the root to the tip, as (position, normal, uv)
+        let mut rows = Vec::with_capacity(segments as usize + 1);
+        for i in 0..=segments {
+            let t = i as f32 / segments as f32;
+            let z = self.lean * t + self.curvature * t * t;
+            // the derivative of the center line is (0, 1, lean + 2 * curvature * t)
+            let normal = Vec3::new(0., -(self.lean + 2. * self.curvature * t), 1.).normalize();
+            let half_width = (self.base_width + (self.tip_width - self.base_width) * t) / 2.;
+            rows.push((Vec3::new(0., t, z), half_width, normal, 1. - t));
+        }
+        let pointed = self.tip_width <= 0.;
+
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut uvs: Vec<[f32; 2]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut push = |position: Vec3, normal: Vec3, uv: [f32; 2]| {
+            positions.push(position.to_array());
+            normals.push(normal.to_array());
+            uvs.push(uv);
+            positions.len() as u32 - 1
+        };
+        match self.style {
+            BladeStyle::DoubleSidedQuads => {
+                for side in [1., -1.] {
+                    let mut previous: Option<(u32, u32)> = None;
+                    for (i, (center, half_width, normal, v)) in rows.iter().enumerate() {
+                        let normal = *normal * side;
+                        let tip = pointed && i == rows.len() - 1;
+                        let left = push(*center - Vec3::X * *half_width, normal, [0., *v]);
+                        let right = if tip {
+                            left
+                        } else {
+                            push(*center + Vec3::X * *half_width, normal, [1., *v])
+                        };
+                        if let Some((prev_left, prev_right)) = previous {
+                            let mut quad = vec![prev_left, prev_right, right];
+                            if !tip {
+                                quad.extend([prev_left, right, left]);
+                            }
+                            if side < 0. {
+                                // the back side is wound the other way around
+                                quad.reverse();
+                            }
+                            indices.extend(quad);
+                        }
+                        previous = Some((left, right));
+                    }
+                }
+            }
+            BladeStyle::TriangleFan => {
+                let (root, _, root_normal, _) = rows[0];
+                let root = push(root, root_normal, [0.5, 1.]);
+                // the outline goes up the right edge and down the left edge
+                let mut outline = Vec::new();
+                for (i, (center, half_width, normal, v)) in rows.iter().enumerate() {
+                    if pointed && i == rows.len() - 1 {
+                        outline.push(push(*center, *normal, [0.5, *v]));
+                    } else {
+                        outline.push(push(*center + Vec3::X * *half_width, *normal, [1., *v]));
+                    }
+                }
+                for (i, (center, half_width, normal, v)) in rows.iter().enumerate().rev() {
+                    if pointed && i == rows.len() - 1 {
+                        continue;
+                    }
+                    outline.push(push(*center - Vec3::X * *half_width, *normal, [0., *v]));
+                }
+                for edge in outline.windows(2) {
+                    indices.extend([root, edge[0], edge[1]]);
+                    indices.extend([root, edge[1], edge[0]]);
+                }
+            }
+        }
+
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_indices(Indices::U32(indices));
+        if self.normals {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        }
+        if self.uvs {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        }
+        mesh
+    }
+    /// Builds a series of `levels` meshes with decreasing detail, starting with the full mesh.
+    ///
+    /// Every level halves the amount of segments, until a single segment is left
+    pub fn build_lods(&self, levels: usize) -> Vec<Mesh> {
+        let mut builder = self.clone();
+        let mut lods = Vec::with_capacity(levels);
+        for _ in 0..levels {
+            lods.push(builder.build());
+            builder.segments = (builder.segments / 2).max(1);
+        }
+        lods
+    }
+}
+impl From<GrassBladeMeshBuilder> for Mesh {
+    fn from(builder: GrassBladeMeshBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{Mesh, VertexAttributeValues};
+
+    use super::{BladeStyle, GrassBladeMeshBuilder};
+
+    fn positions(mesh: &Mesh) -> &Vec<[f32; 3]> {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => panic!("mesh has no positions"),
+        }
+    }
+    #[test]
+    fn blade_shape() {
+        for style in [BladeStyle::DoubleSidedQuads, BladeStyle::TriangleFan] {
+            let mesh = GrassBladeMeshBuilder::new()
+                .with_segments(4)
+                .with_style(style)
+                .build();
+            let positions = positions(&mesh);
+            let top = positions.iter().map(|p| p[1]).fold(0., f32::max);
+            assert_eq!(top, 1.);
+            assert!(positions.iter().all(|p| p[1] >= 0.));
+            assert_eq!(
+                mesh.attribute(Mesh::ATTRIBUTE_NORMAL).unwrap().len(),
+                positions.len()
+            );
+            assert!(mesh.indices().unwrap().iter().all(|i| i < positions.len()));
+        }
+        // 4 rows with two vertices and a pointed tip, for both sides
+        let quads = GrassBladeMeshBuilder::new().with_segments(4).build();
+        assert_eq!(positions(&quads).len(), 2 * 9);
+        assert_eq!(quads.indices().unwrap().len(), 2 * (3 * 6 + 3));
+    }
+    #[test]
+    fn optional_attributes() {
+        let mesh = GrassBladeMeshBuilder::new()
+            .with_attributes(false, false)
+            .build();
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_none());
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_NORMAL).is_none());
+    }
+    #[test]
+    fn lods_reduce_segments() {
+        let lods = GrassBladeMeshBuilder::new().with_segments(8).build_lods(5);
+        let vertices: Vec<usize> = lods.iter().map(|lod| positions(lod).len()).collect();
+        // 8, 4, 2, 1 and again 1 segments
+        assert_eq!(vertices, vec![34, 18, 10, 6, 6]);
+    }
+}
@@ -16,12 +16,18 @@ use crate::{
 };
 
 /// This [`Bundle`] spawns a grass chunk in the world.
+///
+/// Like other meshes, a chunk is only drawn by the cameras it is visible in.
+/// Adding [`RenderLayers`](bevy::render::view::RenderLayers) to the chunk restricts it to the cameras with matching layers.
 #[derive(Bundle)]
 pub struct WarblersBundle {
     /// The [`Mesh`] of the grass blades
     ///
     /// Defaults to the mesh seen in the examples.
     /// The mesh may also be changed at runtime.
+    /// If the mesh has [`Mesh::ATTRIBUTE_COLOR`], the vertex colors are multiplied with the [`GrassColor`].
+    /// If it has [`Mesh::ATTRIBUTE_NORMAL`], the normals are aligned with the [`NormalMap`].
+    /// Add [`GrassLighting`] to the chunk to light the blades with the normals.
     /// You might want to take a look at the
     /// `grass_mesh` example for that
     pub grass_mesh: Handle<Mesh>,
@@ -91,6 +97,131 @@ impl Default for GrassColor {
         }
     }
 }
+/// A texture applied to the grass blades.
+///
+/// The texture is sampled with the [`Mesh::ATTRIBUTE_UV_0`] of the grass mesh and multiplied with the [`GrassColor`].
+/// Pixels with an alpha below the `alpha_cutoff` are discarded, which allows textured cards like flowers or ferns.
+/// If MSAA is enabled, alpha to coverage is used to smooth the edges.
+/// Chunks whose mesh has no uvs aren't drawn and are reported with a [`GrassRenderErrorEvent`](crate::render_error::GrassRenderErrorEvent).
+///
+/// Can be added to a chunk spawned with the [`WarblersBundle`]
+#[derive(Component, Clone, Debug)]
+pub struct GrassAlbedo {
+    /// The texture of the blades
+    pub texture: Handle<Image>,
+    /// Pixels with an alpha below this value are discarded
+    pub alpha_cutoff: f32,
+}
+impl GrassAlbedo {
+    /// Creates a new `GrassAlbedo` with an alpha cutoff of 0.5
+    pub fn new(texture: Handle<Image>) -> Self {
+        GrassAlbedo {
+            texture,
+            alpha_cutoff: 0.5,
+        }
+    }
+}
+impl ExtractComponent for GrassAlbedo {
+    type QueryData = &'static Self;
+
+    type QueryFilter = ();
+
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        Some(GrassAlbedo {
+            texture: item.texture.clone_weak(),
+            alpha_cutoff: item.alpha_cutoff,
+        })
+    }
+}
+/// Turns the blades of a chunk around their up axis, so that they face the camera.
+///
+/// The side of the grass mesh facing the z axis is turned towards the camera,
+/// while the blades stay aligned with the [`NormalMap`].
+/// Useful for grass cards in far fields or stylized 2.5D games.
+///
+/// Can be added to a chunk spawned with the [`WarblersBundle`]
+#[derive(Component, Clone, Copy, Debug, Default, ExtractComponent)]
+pub struct GrassBillboard;
+/// Lights the blades of a chunk with the directional lights of the scene.
+///
+/// Uses the [`Mesh::ATTRIBUTE_NORMAL`] of the grass mesh, aligned with the [`NormalMap`].
+/// Blades are lit from both sides, since they are thin.
+///
+/// Can be added to a chunk spawned with the [`WarblersBundle`]
+#[derive(Component, Clone, Copy, Debug, Default, ExtractComponent)]
+pub struct GrassLighting;
+/// Swaps the grass mesh of a chunk for blades far away from the camera.
+///
+/// The blades of a chunk are grouped in cells, and each cell uses the mesh of the farthest level
+/// whose distance is closer than the camera. Cells closer than all levels use the mesh of the chunk.
+/// The meshes of the levels need to have the same vertex attributes as the mesh of the chunk,
+/// for example the lods built by the [`GrassBladeMeshBuilder`](crate::blade_mesh::GrassBladeMeshBuilder).
+///
+/// Can be added to a chunk spawned with the [`WarblersBundle`]
+#[derive(Component, Clone, Debug, Default)]
+pub struct GrassLods {
+    levels: Vec<GrassLod>,
+}
+/// A level of the [`GrassLods`]
+#[derive(Clone, Debug)]
+pub struct GrassLod {
+    /// The [`Mesh`] of the blades in this level
+    pub mesh: Handle<Mesh>,
+    /// The distance from the camera where this level starts
+    pub distance: f32,
+}
+impl GrassLods {
+    /// Creates new `GrassLods` from the given levels
+    pub fn new(levels: impl IntoIterator<Item = GrassLod>) -> Self {
+        let mut lods = GrassLods {
+            levels: levels.into_iter().collect(),
+        };
+        lods.levels
+            .sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        lods
+    }
+    /// Adds a level using the `mesh` starting at the `distance`
+    pub fn with_level(mut self, mesh: Handle<Mesh>, distance: f32) -> Self {
+        self.levels.push(GrassLod { mesh, distance });
+        self.levels
+            .sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        self
+    }
+    /// The levels sorted by their distance
+    pub fn levels(&self) -> &[GrassLod] {
+        &self.levels
+    }
+    /// Returns the mesh used at the `distance`, or `None` if the mesh of the chunk is used
+    pub fn mesh_at(&self, distance: f32) -> Option<&Handle<Mesh>> {
+        self.levels
+            .iter()
+            .take_while(|level| level.distance <= distance)
+            .last()
+            .map(|level| &level.mesh)
+    }
+}
+impl ExtractComponent for GrassLods {
+    type QueryData = &'static Self;
+
+    type QueryFilter = ();
+
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        Some(GrassLods {
+            levels: item
+                .levels
+                .iter()
+                .map(|level| GrassLod {
+                    mesh: level.mesh.clone_weak(),
+                    distance: level.distance,
+                })
+                .collect(),
+        })
+    }
+}
 impl ExtractComponent for WarblerHeight {
     type QueryData = &'static Self;
 
@@ -105,3 +236,23 @@ impl ExtractComponent for WarblerHeight {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::Handle, render::mesh::Mesh};
+
+    use super::GrassLods;
+
+    #[test]
+    fn lod_levels_by_distance() {
+        let near = Handle::<Mesh>::weak_from_u128(1);
+        let far = Handle::<Mesh>::weak_from_u128(2);
+        let lods = GrassLods::default()
+            .with_level(far.clone(), 50.)
+            .with_level(near.clone(), 20.);
+        assert_eq!(lods.mesh_at(10.), None);
+        assert_eq!(lods.mesh_at(20.), Some(&near));
+        assert_eq!(lods.mesh_at(49.), Some(&near));
+        assert_eq!(lods.mesh_at(100.), Some(&far));
+    }
+}
@@ -0,0 +1,198 @@
+//! Contains the [`GrassChunkAsset`], which describes a grass chunk in a RON file.
+//!
+//! Chunk files use the `.grass.ron` extension and the format of the [`GrassChunkManifest`],
+//! so chunks saved with [`save_chunk`](crate::persistence::save_chunk) can be loaded as assets.
+//! The maps are referenced relative to the chunk file, the mesh by its asset path.
+//! ```ron
+//! (
+//!     aabb_min: (0.0, 0.0, 0.0),
+//!     aabb_max: (100.0, 10.0, 100.0),
+//!     density: 1.0,
+//!     density_map: "density_map.png",
+//!     y_map: "y_map.png",
+//!     height: Uniform(1.5),
+//!     main_color: (0.15, 0.66, 0.1, 1.0),
+//!     bottom_color: (0.1, 0.3, 0.05, 1.0),
+//! )
+//! ```
+//! A chunk is spawned with the [`GrassChunkBundle`].
+//! Once the asset is loaded, the components of the [`WarblersBundle`](crate::prelude::WarblersBundle) are inserted.
+//! They are replaced whenever the asset is reloaded
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{batching::NoAutomaticBatching, primitives::Aabb},
+    utils::{BoxedFuture, HashSet},
+};
+
+use crate::{
+    map::{ColorMap, DensityMap, NormalMap, YMap},
+    persistence::{decode_map, GrassChunkManifest, GrassFileError, SavedHeight},
+    prelude::{GrassColor, WarblerHeight},
+    warblers_plugin::{DEFAULT_NORMAL_MAP_HANDLE, GRASS_MESH_HANDLE},
+};
+
+/// A grass chunk loaded from a `.grass.ron` file.
+///
+/// Holds the components that are inserted into the entities pointing at the asset
+#[derive(Asset, TypePath, Clone)]
+pub struct GrassChunkAsset {
+    #[dependency]
+    pub grass_mesh: Handle<Mesh>,
+    pub y_map: YMap,
+    pub normal_map: NormalMap,
+    pub density_map: DensityMap,
+    pub height: WarblerHeight,
+    pub grass_color: GrassColor,
+    pub color_map: Option<ColorMap>,
+    pub aabb: Aabb,
+}
+
+/// Spawns a grass chunk from a [`GrassChunkAsset`]
+#[derive(Bundle)]
+pub struct GrassChunkBundle {
+    pub chunk: Handle<GrassChunkAsset>,
+    pub spatial: SpatialBundle,
+    pub no_automatic_batching: NoAutomaticBatching,
+}
+impl GrassChunkBundle {
+    pub fn new(chunk: Handle<GrassChunkAsset>) -> Self {
+        GrassChunkBundle {
+            chunk,
+            spatial: SpatialBundle::default(),
+            no_automatic_batching: NoAutomaticBatching,
+        }
+    }
+}
+
+/// Loads [`GrassChunkAsset`]s from `.grass.ron` files.
+///
+/// The maps are added as labeled assets of the chunk, so changing them reloads the chunk as well
+#[derive(Default)]
+pub struct GrassChunkAssetLoader;
+impl AssetLoader for GrassChunkAssetLoader {
+    type Asset = GrassChunkAsset;
+    type Settings = ();
+    type Error = GrassFileError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<GrassChunkAsset, GrassFileError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let manifest: GrassChunkManifest = ron::de::from_bytes(&bytes)?;
+
+            let density_map =
+                load_map(load_context, "density_map", &manifest.density_map, false).await?;
+            let y_map = load_map(load_context, "y_map", &manifest.y_map, false).await?;
+            let normal_map = match &manifest.normal_map {
+                Some(file) => load_map(load_context, "normal_map", file, false).await?,
+                None => DEFAULT_NORMAL_MAP_HANDLE,
+            };
+            let height = match &manifest.height {
+                SavedHeight::Uniform(height) => WarblerHeight::Uniform(*height),
+                SavedHeight::Texture(file) => {
+                    WarblerHeight::Texture(load_map(load_context, "height_map", file, false).await?)
+                }
+            };
+            let color_map = match &manifest.color_map {
+                Some(file) => Some(load_map(load_context, "color_map", file, true).await?),
+                None => None,
+            };
+            let grass_mesh = match &manifest.mesh {
+                Some(mesh) => load_context.load(mesh.clone()),
+                None => GRASS_MESH_HANDLE,
+            };
+
+            Ok(GrassChunkAsset {
+                grass_mesh,
+                y_map: YMap::new(y_map),
+                normal_map: NormalMap::new(normal_map),
+                density_map: DensityMap::new(density_map, manifest.density),
+                height,
+                grass_color: GrassColor {
+                    main_color: Color::rgba_from_array(manifest.main_color),
+                    bottom_color: Color::rgba_from_array(manifest.bottom_color),
+                },
+                color_map: color_map.map(ColorMap::new),
+                aabb: Aabb::from_min_max(manifest.aabb_min.into(), manifest.aabb_max.into()),
+            })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["grass.ron"]
+    }
+}
+/// Reads a map relative to the chunk file and adds it as a labeled asset
+async fn load_map(
+    load_context: &mut LoadContext<'_>,
+    label: &str,
+    file: &str,
+    is_srgb: bool,
+) -> Result<Handle<Image>, GrassFileError> {
+    let path = load_context.asset_path().resolve_embed(file)?;
+    let bytes = load_context.read_asset_bytes(path).await?;
+    let image = decode_map(&bytes, is_srgb)?;
+    Ok(load_context.add_labeled_asset(label.to_string(), image))
+}
+
+/// Inserts the components of the [`GrassChunkAsset`]s into the chunks pointing at them,
+/// once the asset is loaded or whenever it changes
+pub(crate) fn insert_chunk_assets(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<GrassChunkAsset>>,
+    assets: Res<Assets<GrassChunkAsset>>,
+    chunks: Query<(Entity, Ref<Handle<GrassChunkAsset>>)>,
+) {
+    let loaded: HashSet<AssetId<GrassChunkAsset>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, handle) in &chunks {
+        if !handle.is_changed() && !loaded.contains(&handle.id()) {
+            continue;
+        }
+        let Some(chunk) = assets.get(&*handle) else {
+            continue;
+        };
+        let mut entity = commands.entity(entity);
+        entity.insert((
+            chunk.grass_mesh.clone(),
+            chunk.y_map.clone(),
+            chunk.normal_map.clone(),
+            chunk.density_map.clone(),
+            chunk.height.clone(),
+            chunk.grass_color.clone(),
+            chunk.aabb,
+        ));
+        match &chunk.color_map {
+            Some(color_map) => entity.insert(color_map.clone()),
+            None => entity.remove::<ColorMap>(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::persistence::{GrassChunkManifest, SavedHeight};
+
+    #[test]
+    fn example_chunk_is_valid() {
+        let manifest: GrassChunkManifest =
+            ron::from_str(include_str!("../assets/meadow.grass.ron")).unwrap();
+        assert_eq!(
+            manifest.height,
+            SavedHeight::Texture("grass_heights_map.png".into())
+        );
+        // optional fields can be left out
+        assert_eq!((manifest.color_map, manifest.mesh), (None, None));
+    }
+}
@@ -0,0 +1,454 @@
+//! Contains the api to cut or flatten grass blades at runtime.
+//!
+//! Send a [`GrassCut`] event to remove or flatten all blades inside of a [`GrassShape`].
+//! The affected chunks receive a [`GrassBladeStates`] component, which stores the state of each blade
+//! and is updated incrementally on the gpu.
+use std::ops::Range;
+
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    render::primitives::Aabb,
+    utils::{HashMap, HashSet},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::dithering::DitheredBuffer;
+
+/// A shape on the x,z plane in world space
+#[derive(Clone, Debug, PartialEq)]
+pub enum GrassShape {
+    /// A circle around `center` with the given `radius`
+    Circle { center: Vec2, radius: f32 },
+    /// All points with a distance of at most `radius` to the line between `start` and `end`
+    Capsule { start: Vec2, end: Vec2, radius: f32 },
+    /// A polygon given by its corners.
+    ///
+    /// The polygon is closed automatically and doesn't have to be convex
+    Polygon(Vec<Vec2>),
+}
+impl GrassShape {
+    /// Returns true if the point lies inside of the shape
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            GrassShape::Circle { center, radius } => {
+                point.distance_squared(*center) <= radius * radius
+            }
+            GrassShape::Capsule { start, end, radius } => {
+                let segment = *end - *start;
+                let t = if segment == Vec2::ZERO {
+                    0.
+                } else {
+                    ((point - *start).dot(segment) / segment.length_squared()).clamp(0., 1.)
+                };
+                point.distance_squared(*start + segment * t) <= radius * radius
+            }
+            GrassShape::Polygon(corners) => {
+                // even-odd rule
+                let mut inside = false;
+                let mut previous = match corners.last() {
+                    Some(last) => *last,
+                    None => return false,
+                };
+                for corner in corners {
+                    if (corner.y > point.y) != (previous.y > point.y)
+                        && point.x
+                            < (previous.x - corner.x) * (point.y - corner.y)
+                                / (previous.y - corner.y)
+                                + corner.x
+                    {
+                        inside = !inside;
+                    }
+                    previous = *corner;
+                }
+                inside
+            }
+        }
+    }
+    /// The minimum and maximum corner of the rectangle containing the shape
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            GrassShape::Circle { center, radius } => (
+                *center - Vec2::splat(*radius),
+                *center + Vec2::splat(*radius),
+            ),
+            GrassShape::Capsule { start, end, radius } => (
+                start.min(*end) - Vec2::splat(*radius),
+                start.max(*end) + Vec2::splat(*radius),
+            ),
+            GrassShape::Polygon(corners) => corners.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), corner| (min.min(*corner), max.max(*corner)),
+            ),
+        }
+    }
+}
+/// Defines what happens to the blades inside of a [`GrassShape`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrassCutMode {
+    /// The blades are removed completely
+    Remove,
+    /// The blades are bent to the ground
+    Flatten,
+}
+/// An [`Event`] that cuts or flattens all grass blades in a shape
+///
+/// All chunks overlapping the shape are affected
+#[derive(Event, Clone, Debug)]
+pub struct GrassCut {
+    /// The shape in world space
+    pub shape: GrassShape,
+    /// What should happen to the blades
+    pub mode: GrassCutMode,
+    /// The time in seconds until the blades are fully grown again.
+    ///
+    /// If `None`, the blades never grow back
+    pub regrowth: Option<f32>,
+}
+/// The state of a single grass blade as uploaded to the gpu
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct BladeState {
+    /// How much of the blade is left. 0 means the blade was removed
+    pub growth: f32,
+    /// How flat the blade is. 1 means the blade lies on the ground
+    pub flatten: f32,
+}
+impl Default for BladeState {
+    fn default() -> Self {
+        BladeState {
+            growth: 1.,
+            flatten: 0.,
+        }
+    }
+}
+/// The state of each blade of a chunk.
+///
+/// Inserted automatically on the first [`GrassCut`] affecting the chunk.
+/// If the blades are dithered again, for example when the [`DensityMap`](crate::map::DensityMap) changes,
+/// the states are kept for all blades that didn't move. New blades are fully grown
+#[derive(Component, Clone, Debug, Default)]
+pub struct GrassBladeStates {
+    states: Vec<BladeState>,
+    /// The chunk local positions of the blades the states belong to
+    positions: Vec<Vec2>,
+    /// The regrowth per second of each blade
+    regrowth_rates: Vec<f32>,
+    /// Indices of blades that are currently growing back
+    growing: Vec<usize>,
+    /// The range of states that changed since the last frame
+    pub(crate) dirty: Option<Range<usize>>,
+    /// Set if the whole buffer has to be uploaded again
+    pub(crate) resized: bool,
+}
+impl GrassBladeStates {
+    /// Creates the states for the given amount of fully grown blades
+    pub fn new(blade_count: usize) -> Self {
+        GrassBladeStates {
+            states: vec![BladeState::default(); blade_count],
+            positions: Vec::new(),
+            regrowth_rates: vec![0.; blade_count],
+            growing: Vec::new(),
+            dirty: None,
+            resized: true,
+        }
+    }
+    /// The states of all blades
+    pub fn states(&self) -> &[BladeState] {
+        &self.states
+    }
+    /// Cuts the blades with the given chunk local positions that lie inside of the cut shape
+    ///
+    /// Note that the blades are randomly offset by up to half a unit in the shader,
+    /// so blades close to the border of the shape might seem to be on the wrong side
+    ///
+    /// Returns the amount of affected blades
+    pub fn cut(
+        &mut self,
+        positions: &[Vec2],
+        transform: &GlobalTransform,
+        cut: &GrassCut,
+    ) -> usize {
+        if self.positions != positions {
+            self.remap(positions);
+        }
+        let rate = cut
+            .regrowth
+            .map_or(0., |seconds| 1. / seconds.max(f32::EPSILON));
+        let mut affected = 0;
+        for (index, position) in positions.iter().enumerate() {
+            let world = transform
+                .transform_point(Vec3::new(position.x, 0., position.y))
+                .xz();
+            if !cut.shape.contains(world) {
+                continue;
+            }
+            let state = &mut self.states[index];
+            match cut.mode {
+                GrassCutMode::Remove => state.growth = 0.,
+                GrassCutMode::Flatten => state.flatten = 1.,
+            }
+            if rate > 0. {
+                self.growing.push(index);
+            }
+            self.regrowth_rates[index] = rate;
+            self.mark_dirty(index);
+            affected += 1;
+        }
+        affected
+    }
+    /// Grows all blades with a regrowth timer by the elapsed time in seconds
+    pub fn regrow(&mut self, delta: f32) {
+        let mut growing = std::mem::take(&mut self.growing);
+        // a blade might have been cut multiple times
+        growing.sort_unstable();
+        growing.dedup();
+        growing.retain(|&index| {
+            let rate = self.regrowth_rates[index];
+            let state = &mut self.states[index];
+            state.growth = (state.growth + rate * delta).min(1.);
+            state.flatten = (state.flatten - rate * delta).max(0.);
+            let finished = rate == 0. || *state == BladeState::default();
+            if finished {
+                self.regrowth_rates[index] = 0.;
+            }
+            self.mark_dirty(index);
+            !finished
+        });
+        self.growing = growing;
+    }
+    /// Moves the states to the blades at the same positions after the blades were dithered again.
+    ///
+    /// Dithering the same density map gives the same positions, but they might be in a different order
+    fn remap(&mut self, positions: &[Vec2]) {
+        let key = |position: &Vec2| (position.x.to_bits(), position.y.to_bits());
+        // the positions are unknown for states created with `new`
+        let old: HashMap<(u32, u32), usize> = if self.positions.len() == self.states.len() {
+            self.positions
+                .iter()
+                .enumerate()
+                .map(|(index, position)| (key(position), index))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let mut remapped = GrassBladeStates::new(positions.len());
+        for (index, position) in positions.iter().enumerate() {
+            let Some(&old_index) = old.get(&key(position)) else {
+                continue;
+            };
+            remapped.states[index] = self.states[old_index];
+            let rate = self.regrowth_rates[old_index];
+            remapped.regrowth_rates[index] = rate;
+            if rate > 0. && remapped.states[index] != BladeState::default() {
+                remapped.growing.push(index);
+            }
+        }
+        remapped.positions = positions.to_vec();
+        *self = remapped;
+    }
+    fn mark_dirty(&mut self, index: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(range) => range.start.min(index)..range.end.max(index + 1),
+            None => index..index + 1,
+        });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn apply_grass_cuts(
+    mut commands: Commands,
+    mut cuts: EventReader<GrassCut>,
+    mut chunks: Query<(
+        Entity,
+        &Handle<DitheredBuffer>,
+        &GlobalTransform,
+        &Aabb,
+        Option<&mut GrassBladeStates>,
+    )>,
+    dithered: Res<Assets<DitheredBuffer>>,
+    mut inserted: Local<HashMap<Entity, GrassBladeStates>>,
+) {
+    for cut in cuts.read() {
+        let (shape_min, shape_max) = cut.shape.bounds();
+        for (entity, handle, transform, aabb, states) in &mut chunks {
+            let Some(buffer) = dithered.get(handle) else {
+                continue;
+            };
+            // skip chunks that can't overlap the shape
+            let (chunk_min, chunk_max) = world_bounds(aabb, transform);
+            if chunk_min.cmpgt(shape_max).any() || chunk_max.cmplt(shape_min).any() {
+                continue;
+            }
+            match states {
+                Some(mut states) => {
+                    states.cut(&buffer.positions, transform, cut);
+                }
+                None => {
+                    // the chunk might be affected by multiple cuts in the same frame
+                    let states = inserted
+                        .entry(entity)
+                        .or_insert_with(|| GrassBladeStates::new(buffer.positions.len()));
+                    states.cut(&buffer.positions, transform, cut);
+                }
+            }
+        }
+    }
+    for (entity, states) in inserted.drain() {
+        if states.dirty.is_some() {
+            commands.entity(entity).try_insert(states);
+        }
+    }
+}
+/// The rectangle on the x,z plane covered by the chunk
+fn world_bounds(aabb: &Aabb, transform: &GlobalTransform) -> (Vec2, Vec2) {
+    let min = Vec3::from(aabb.min());
+    let max = Vec3::from(aabb.max());
+    let corners = [
+        Vec3::new(min.x, 0., min.z),
+        Vec3::new(max.x, 0., min.z),
+        Vec3::new(min.x, 0., max.z),
+        Vec3::new(max.x, 0., max.z),
+    ];
+    corners.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), corner| {
+            let corner = transform.transform_point(*corner).xz();
+            (min.min(corner), max.max(corner))
+        },
+    )
+}
+pub(crate) fn regrow_blades(time: Res<Time>, mut chunks: Query<&mut GrassBladeStates>) {
+    let delta = time.delta_seconds();
+    for mut states in &mut chunks {
+        if !states.growing.is_empty() {
+            states.regrow(delta);
+        }
+    }
+}
+/// Moves the states to the new blades if the blades were dithered again
+pub(crate) fn reset_blade_states(
+    mut chunks: Query<(Ref<Handle<DitheredBuffer>>, &mut GrassBladeStates)>,
+    mut events: EventReader<AssetEvent<DitheredBuffer>>,
+    dithered: Res<Assets<DitheredBuffer>>,
+) {
+    let modified: HashSet<AssetId<DitheredBuffer>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (handle, mut states) in &mut chunks {
+        if !handle.is_changed() && !modified.contains(&handle.id()) {
+            continue;
+        }
+        let Some(buffer) = dithered.get(&*handle) else {
+            continue;
+        };
+        if states.positions != buffer.positions {
+            states.remap(&buffer.positions);
+        }
+    }
+}
+/// The changes of the last frame were extracted, so we can forget about them
+pub(crate) fn clear_dirty_blade_states(mut chunks: Query<&mut GrassBladeStates>) {
+    for mut states in &mut chunks {
+        if states.dirty.is_some() || states.resized {
+            let states = states.bypass_change_detection();
+            states.dirty = None;
+            states.resized = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{GlobalTransform, Transform, Vec2, Vec3};
+
+    use super::{BladeState, GrassBladeStates, GrassCut, GrassCutMode, GrassShape};
+
+    #[test]
+    fn shapes() {
+        let circle = GrassShape::Circle {
+            center: Vec2::new(1., 1.),
+            radius: 1.,
+        };
+        assert!(circle.contains(Vec2::new(1.5, 1.5)));
+        assert!(!circle.contains(Vec2::new(2., 2.)));
+
+        let capsule = GrassShape::Capsule {
+            start: Vec2::ZERO,
+            end: Vec2::new(10., 0.),
+            radius: 1.,
+        };
+        assert!(capsule.contains(Vec2::new(5., 0.9)));
+        assert!(capsule.contains(Vec2::new(10.5, 0.)));
+        assert!(!capsule.contains(Vec2::new(5., 1.1)));
+
+        // a L-shaped polygon
+        let polygon = GrassShape::Polygon(vec![
+            Vec2::ZERO,
+            Vec2::new(2., 0.),
+            Vec2::new(2., 1.),
+            Vec2::new(1., 1.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+        ]);
+        assert!(polygon.contains(Vec2::new(0.5, 1.5)));
+        assert!(polygon.contains(Vec2::new(1.5, 0.5)));
+        assert!(!polygon.contains(Vec2::new(1.5, 1.5)));
+        assert!(!GrassShape::Polygon(Vec::new()).contains(Vec2::ZERO));
+    }
+    #[test]
+    fn cut_and_regrow() {
+        let positions = [Vec2::new(0., 0.), Vec2::new(5., 5.), Vec2::new(9., 9.)];
+        let mut states = GrassBladeStates::new(positions.len());
+        // the chunk is moved, so the blades are at (10,10), (15,15) and (19,19) in world space
+        let transform = GlobalTransform::from(Transform::from_translation(Vec3::new(10., 0., 10.)));
+        let cut = GrassCut {
+            shape: GrassShape::Circle {
+                center: Vec2::new(15., 15.),
+                radius: 1.,
+            },
+            mode: GrassCutMode::Remove,
+            regrowth: Some(2.),
+        };
+        assert_eq!(states.cut(&positions, &transform, &cut), 1);
+        assert_eq!(states.states()[1].growth, 0.);
+        assert_eq!(states.states()[0], BladeState::default());
+        assert_eq!(states.dirty, Some(1..2));
+
+        states.regrow(1.);
+        assert_eq!(states.states()[1].growth, 0.5);
+        states.regrow(1.);
+        assert_eq!(states.states()[1], BladeState::default());
+        assert!(states.growing.is_empty());
+    }
+    #[test]
+    fn states_follow_the_blades_after_dithering_again() {
+        let positions = [Vec2::new(0., 0.), Vec2::new(5., 5.), Vec2::new(9., 9.)];
+        let mut states = GrassBladeStates::new(positions.len());
+        let cut = GrassCut {
+            shape: GrassShape::Circle {
+                center: Vec2::new(5., 5.),
+                radius: 1.,
+            },
+            mode: GrassCutMode::Flatten,
+            regrowth: Some(2.),
+        };
+        states.cut(&positions, &GlobalTransform::IDENTITY, &cut);
+
+        // a region was dithered again, the blade at (9,9) moved and the blades are in a new order
+        let redithered = [Vec2::new(5., 5.), Vec2::new(8., 9.), Vec2::new(0., 0.)];
+        states.remap(&redithered);
+        assert_eq!(states.states()[0].flatten, 1.);
+        assert_eq!(states.states()[1], BladeState::default());
+        assert_eq!(states.states()[2], BladeState::default());
+        assert!(states.resized);
+
+        // the flattened blade keeps growing back
+        states.regrow(1.);
+        assert_eq!(states.states()[0].flatten, 0.5);
+    }
+}
@@ -0,0 +1,393 @@
+//! Contains the [`WarblerDebugPlugin`], which draws gizmos to inspect grass chunks.
+//!
+//! Requires the `debug` feature of the crate
+use bevy::{
+    prelude::*,
+    render::{primitives::Aabb, render_resource::TextureFormat},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    dithering::{ComputeDither, DitheredBuffer, GrassComputeError, GrassComputeEvent},
+    map::{DensityMap, YMap},
+    render_error::GrassRenderErrorEvent,
+};
+
+/// A [`Plugin`] that draws [`Gizmos`] for all grass chunks.
+///
+/// By default the [`Aabb`] of every chunk is drawn, colored by the status of the chunk.
+/// Further overlays can be enabled in the [`WarblerDebugSettings`].
+///
+/// Should be added after the [`WarblersPlugin`](crate::prelude::WarblersPlugin).
+/// The gizmos are only drawn if the `GizmoPlugin` is added, which is part of the `DefaultPlugins`
+/// # Example
+/// ```rust
+/// use bevy::prelude::App;
+/// use warbler_grass::debug::{WarblerDebugPlugin, WarblerDebugSettings};
+///
+/// App::new()
+///     .add_plugins(WarblerDebugPlugin)
+///     // draws the position of every blade in addition to the aabbs
+///     .insert_resource(WarblerDebugSettings {
+///         blade_positions: true,
+///         ..Default::default()
+///     });
+/// ```
+pub struct WarblerDebugPlugin;
+impl Plugin for WarblerDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WarblerDebugSettings>()
+            .init_resource::<ChunkErrors>()
+            .init_resource::<SampledMaps>()
+            .add_systems(
+                Update,
+                (
+                    (track_chunk_errors, update_sampled_maps),
+                    (draw_aabbs, draw_map_overlays, draw_blade_positions),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Selects the gizmos drawn by the [`WarblerDebugPlugin`]
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct WarblerDebugSettings {
+    /// Draws the [`Aabb`] of each chunk, colored by its [`ChunkStatus`]
+    pub aabbs: bool,
+    /// Draws the [`DensityMap`] as a grid at the bottom of the chunk.
+    ///
+    /// Dense areas are green, sparse areas dark
+    pub density_map: bool,
+    /// Draws the [`YMap`] as a grid at the height of the blade roots.
+    ///
+    /// Low areas are blue, high areas red
+    pub y_map: bool,
+    /// Draws a short vertical line at the root of every blade
+    pub blade_positions: bool,
+    /// The amount of grid cells per side of the map overlays
+    pub overlay_resolution: u32,
+    /// The maximum amount of blades drawn per chunk if [`blade_positions`](Self::blade_positions) is enabled.
+    ///
+    /// Larger chunks only show every n-th blade
+    pub max_blade_positions: usize,
+}
+impl Default for WarblerDebugSettings {
+    fn default() -> Self {
+        WarblerDebugSettings {
+            aabbs: true,
+            density_map: false,
+            y_map: false,
+            blade_positions: false,
+            overlay_resolution: 32,
+            max_blade_positions: 10_000,
+        }
+    }
+}
+
+/// The status of a grass chunk, as shown by the [`WarblerDebugPlugin`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkStatus {
+    /// The density map is still loading or being dithered
+    Pending,
+    /// The blades of the chunk are ready to be drawn
+    Ready,
+    /// Dithering the density map failed or the chunk can't be drawn
+    Error,
+}
+impl ChunkStatus {
+    /// The color of the gizmos of a chunk with this status
+    pub fn color(&self) -> Color {
+        match self {
+            ChunkStatus::Pending => Color::YELLOW,
+            ChunkStatus::Ready => Color::GREEN,
+            ChunkStatus::Error => Color::RED,
+        }
+    }
+}
+
+/// The chunks which reported an error since their last dithering or mesh change
+#[derive(Resource, Default)]
+struct ChunkErrors(HashSet<Entity>);
+
+fn track_chunk_errors(
+    mut errors: ResMut<ChunkErrors>,
+    mut compute_events: EventReader<GrassComputeEvent>,
+    mut render_errors: EventReader<GrassRenderErrorEvent>,
+    changed_meshes: Query<Entity, Changed<Handle<Mesh>>>,
+) {
+    for entity in &changed_meshes {
+        errors.0.remove(&entity);
+    }
+    for event in compute_events.read() {
+        match event {
+            GrassComputeEvent::StartComputation(entity) => {
+                errors.0.remove(entity);
+            }
+            GrassComputeEvent::Error(GrassComputeError::FailedComputation(entity, _)) => {
+                errors.0.insert(*entity);
+            }
+            _ => {}
+        }
+    }
+    for event in render_errors.read() {
+        errors.0.insert(event.entity);
+    }
+}
+
+/// A map image sampled into values between 0 and 1
+struct SampledMap {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+impl SampledMap {
+    fn from_image(image: &Image) -> Option<Self> {
+        // 16 bit maps loaded by the persistence module can't be converted to a dynamic image
+        if image.texture_descriptor.format == TextureFormat::R32Float {
+            let (width, height) = (image.width() as usize, image.height() as usize);
+            if width == 0 || height == 0 {
+                return None;
+            }
+            return Some(SampledMap {
+                width,
+                height,
+                values: image
+                    .data
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+                    .collect(),
+            });
+        }
+        let luma = image.clone().try_into_dynamic().ok()?.into_luma8();
+        if luma.width() == 0 || luma.height() == 0 {
+            return None;
+        }
+        Some(SampledMap {
+            width: luma.width() as usize,
+            height: luma.height() as usize,
+            values: luma
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / 255.)
+                .collect(),
+        })
+    }
+    /// Samples the value at a position in the chunk, the same way as the grass shader does
+    fn sample(&self, position: Vec2, chunk_size: Vec2) -> f32 {
+        let texel =
+            (position / chunk_size * Vec2::new(self.width as f32, self.height as f32)).abs();
+        let x = (texel.x as usize).min(self.width - 1);
+        let y = (texel.y as usize).min(self.height - 1);
+        self.values[y * self.width + x]
+    }
+}
+
+/// The sampled maps of the chunks, updated if their image changes
+#[derive(Resource, Default)]
+struct SampledMaps(HashMap<AssetId<Image>, SampledMap>);
+impl SampledMaps {
+    fn get(&self, image: &Handle<Image>) -> Option<&SampledMap> {
+        self.0.get(&image.id())
+    }
+}
+
+fn update_sampled_maps(
+    settings: Res<WarblerDebugSettings>,
+    mut sampled: ResMut<SampledMaps>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    chunks: Query<(&DensityMap, Option<&YMap>)>,
+) {
+    for event in image_events.read() {
+        if let AssetEvent::Modified { id } | AssetEvent::Removed { id } = event {
+            sampled.0.remove(id);
+        }
+    }
+    if !(settings.density_map || settings.y_map || settings.blade_positions) {
+        return;
+    }
+    for (density_map, y_map) in &chunks {
+        let maps = [Some(&density_map.density_map), y_map.map(|map| &map.y_map)];
+        for image in maps.into_iter().flatten() {
+            if sampled.0.contains_key(&image.id()) {
+                continue;
+            }
+            let Some(map) = images.get(image).and_then(SampledMap::from_image) else {
+                continue;
+            };
+            sampled.0.insert(image.id(), map);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_aabbs(
+    mut gizmos: Gizmos,
+    settings: Res<WarblerDebugSettings>,
+    errors: Res<ChunkErrors>,
+    chunks: Query<
+        (
+            Entity,
+            &Aabb,
+            &GlobalTransform,
+            Has<ComputeDither>,
+            Has<Handle<DitheredBuffer>>,
+        ),
+        With<DensityMap>,
+    >,
+) {
+    if !settings.aabbs {
+        return;
+    }
+    for (entity, aabb, transform, computing, dithered) in &chunks {
+        let status = if errors.0.contains(&entity) {
+            ChunkStatus::Error
+        } else if dithered && !computing {
+            ChunkStatus::Ready
+        } else {
+            ChunkStatus::Pending
+        };
+        let cuboid = Transform::from_translation(aabb.center.into())
+            .with_scale(Vec3::from(aabb.half_extents) * 2.);
+        gizmos.cuboid(transform.mul_transform(cuboid), status.color());
+    }
+}
+
+fn draw_map_overlays(
+    mut gizmos: Gizmos,
+    settings: Res<WarblerDebugSettings>,
+    sampled: Res<SampledMaps>,
+    chunks: Query<(&Aabb, &GlobalTransform, &DensityMap, Option<&YMap>)>,
+) {
+    if !(settings.density_map || settings.y_map) {
+        return;
+    }
+    let resolution = settings.overlay_resolution.max(1);
+    for (aabb, transform, density_map, y_map) in &chunks {
+        let size = Vec3::from(aabb.half_extents) * 2.;
+        // the overlays are drawn as a grid of rows and columns
+        let mut draw_grid = |point: &dyn Fn(Vec2) -> (Vec3, Color)| {
+            for i in 0..=resolution {
+                let t = i as f32 / resolution as f32;
+                let row = (0..=resolution)
+                    .map(|j| point(Vec2::new(j as f32 / resolution as f32, t) * size.xz()));
+                gizmos.linestrip_gradient(row.map(|(p, c)| (transform.transform_point(p), c)));
+                let column = (0..=resolution)
+                    .map(|j| point(Vec2::new(t, j as f32 / resolution as f32) * size.xz()));
+                gizmos.linestrip_gradient(column.map(|(p, c)| (transform.transform_point(p), c)));
+            }
+        };
+        if settings.density_map {
+            if let Some(map) = sampled.get(&density_map.density_map) {
+                let bottom = aabb.min().y;
+                draw_grid(&|position| {
+                    let density = map.sample(position, size.xz());
+                    (
+                        Vec3::new(position.x, bottom, position.y),
+                        Color::rgb(0.1, 0.1 + 0.9 * density, 0.1),
+                    )
+                });
+            }
+        }
+        if settings.y_map {
+            if let Some(map) = y_map.and_then(|y_map| sampled.get(&y_map.y_map)) {
+                draw_grid(&|position| {
+                    let y = map.sample(position, size.xz());
+                    (
+                        Vec3::new(position.x, y * size.y, position.y),
+                        Color::rgb(y, 0.2, 1. - y),
+                    )
+                });
+            }
+        }
+    }
+}
+
+fn draw_blade_positions(
+    mut gizmos: Gizmos,
+    settings: Res<WarblerDebugSettings>,
+    sampled: Res<SampledMaps>,
+    dithered: Res<Assets<DitheredBuffer>>,
+    chunks: Query<(
+        &Aabb,
+        &GlobalTransform,
+        &Handle<DitheredBuffer>,
+        Option<&YMap>,
+    )>,
+) {
+    if !settings.blade_positions {
+        return;
+    }
+    for (aabb, transform, handle, y_map) in &chunks {
+        let Some(buffer) = dithered.get(handle) else {
+            continue;
+        };
+        let size = Vec3::from(aabb.half_extents) * 2.;
+        let y_map = y_map.and_then(|y_map| sampled.get(&y_map.y_map));
+        let step = buffer
+            .positions
+            .len()
+            .div_ceil(settings.max_blade_positions.max(1))
+            .max(1);
+        for position in buffer.positions.iter().step_by(step) {
+            let y = y_map.map_or(0., |map| map.sample(*position, size.xz()) * size.y);
+            let root = transform.transform_point(Vec3::new(position.x, y, position.y));
+            gizmos.ray(root, transform.up() * 0.2, Color::WHITE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::Vec2,
+        prelude::Image,
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use super::SampledMap;
+
+    #[test]
+    fn maps_are_sampled_like_the_shader() {
+        let image = Image::new(
+            Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![0, 255, 51, 102],
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let map = SampledMap::from_image(&image).unwrap();
+        let size = Vec2::new(10., 10.);
+        assert_eq!(map.sample(Vec2::new(1., 1.), size), 0.);
+        assert_eq!(map.sample(Vec2::new(6., 1.), size), 1.);
+        assert_eq!(map.sample(Vec2::new(1., 6.), size), 0.2);
+        // positions on the far edge are clamped into the map
+        assert_eq!(map.sample(size, size), 0.4);
+    }
+    #[test]
+    fn float_maps_are_sampled() {
+        let values = [0.25f32, 0.5, 0.75, 1.];
+        let image = Image::new(
+            Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            bytemuck::cast_slice(&values).to_vec(),
+            TextureFormat::R32Float,
+            RenderAssetUsages::default(),
+        );
+        let map = SampledMap::from_image(&image).unwrap();
+        let size = Vec2::new(10., 10.);
+        assert_eq!(map.sample(Vec2::new(6., 1.), size), 0.5);
+        assert_eq!(map.sample(Vec2::new(6., 6.), size), 1.);
+    }
+}
@@ -1,12 +1,30 @@
+use std::mem;
+
 use bevy::{
     diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
-    prelude::{Assets, Handle, InheritedVisibility, Plugin, Query, Res, Update},
+    prelude::{Assets, Handle, InheritedVisibility, Plugin, Query, Res, ResMut, Update, With},
     render::view::ViewVisibility,
 };
 
-use crate::dithering::DitheredBuffer;
+use crate::{
+    cutting::GrassBladeStates,
+    dithering::{ComputeDither, DitherTimings, DitheredBuffer},
+    map::DensityMap,
+    render::cache::GrassDrawCalls,
+};
 
-/// A [`Plugin`] that logs the blades drawn in each frame.
+/// A [`Plugin`] that logs statistics about the grass in each frame.
+///
+/// The following values are measured:
+/// - [`GRASS_BLADE_COUNT`](Self::GRASS_BLADE_COUNT): the blades of all visible chunks
+/// - [`CHUNK_COUNT`](Self::CHUNK_COUNT) and [`VISIBLE_CHUNK_COUNT`](Self::VISIBLE_CHUNK_COUNT)
+/// - [`PENDING_DITHER_TASKS`](Self::PENDING_DITHER_TASKS): the chunks whose density map is still being dithered
+/// - [`DITHER_TIME_AVERAGE`](Self::DITHER_TIME_AVERAGE) and [`DITHER_TIME_MAX`](Self::DITHER_TIME_MAX):
+///   the duration of the dithering computations finished in this frame
+/// - [`INSTANCE_DATA_BYTES`](Self::INSTANCE_DATA_BYTES): the size of the per blade data in the main world
+/// - [`DRAW_CALLS`](Self::DRAW_CALLS): the grass draw calls of the last rendered frame
+///
+/// The [`WarblersPlugin`](crate::prelude::WarblersPlugin) has to be added for the dithering and draw call measurements.
 ///
 /// # Example
 /// ```rust
@@ -29,12 +47,77 @@ impl Plugin for WarblerDiagnosticsPlugin {
                 .with_suffix(" blades")
                 .with_max_history_length(20),
         )
-        .add_systems(Update, Self::measure_blades);
+        .register_diagnostic(
+            Diagnostic::new(Self::CHUNK_COUNT)
+                .with_suffix(" chunks")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::VISIBLE_CHUNK_COUNT)
+                .with_suffix(" chunks")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::PENDING_DITHER_TASKS)
+                .with_suffix(" tasks")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::DITHER_TIME_AVERAGE)
+                .with_suffix("ms")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::DITHER_TIME_MAX)
+                .with_suffix("ms")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::INSTANCE_DATA_BYTES)
+                .with_suffix(" bytes")
+                .with_max_history_length(20),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::DRAW_CALLS)
+                .with_suffix(" draw calls")
+                .with_max_history_length(20),
+        )
+        .add_systems(
+            Update,
+            (
+                Self::measure_blades,
+                Self::measure_chunks,
+                Self::measure_dithering,
+                Self::measure_instance_data,
+                Self::measure_draw_calls,
+            ),
+        );
     }
 }
 impl WarblerDiagnosticsPlugin {
     /// An id for the [`Diagnostic`] of the blade count.
     pub const GRASS_BLADE_COUNT: DiagnosticPath = DiagnosticPath::const_new("grass/blade_count");
+    /// An id for the [`Diagnostic`] of the amount of chunks.
+    pub const CHUNK_COUNT: DiagnosticPath = DiagnosticPath::const_new("grass/chunk_count");
+    /// An id for the [`Diagnostic`] of the amount of visible chunks.
+    pub const VISIBLE_CHUNK_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("grass/visible_chunk_count");
+    /// An id for the [`Diagnostic`] of the amount of running dither computations.
+    pub const PENDING_DITHER_TASKS: DiagnosticPath =
+        DiagnosticPath::const_new("grass/pending_dither_tasks");
+    /// An id for the [`Diagnostic`] of the average duration of a dither computation in milliseconds.
+    pub const DITHER_TIME_AVERAGE: DiagnosticPath =
+        DiagnosticPath::const_new("grass/dither_time_average");
+    /// An id for the [`Diagnostic`] of the longest dither computation in milliseconds.
+    pub const DITHER_TIME_MAX: DiagnosticPath = DiagnosticPath::const_new("grass/dither_time_max");
+    /// An id for the [`Diagnostic`] of the size of the blade positions and states in the main world in bytes.
+    ///
+    /// This is the cpu side of the instance data. The buffers on the gpu also hold the instance indices and padding,
+    /// and chunks sharing their blades share the buffers as well
+    pub const INSTANCE_DATA_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("grass/instance_data_bytes");
+    /// An id for the [`Diagnostic`] of the grass draw calls per frame.
+    pub const DRAW_CALLS: DiagnosticPath = DiagnosticPath::const_new("grass/draw_calls");
 
     /// Calculates the amount of blades that are drawn this frame and logs them
     fn measure_blades(
@@ -59,4 +142,69 @@ impl WarblerDiagnosticsPlugin {
 
         diagnostics.add_measurement(&Self::GRASS_BLADE_COUNT, || count as f64);
     }
+
+    /// Logs the amount of chunks and the chunks that are visible this frame
+    fn measure_chunks(
+        chunks: Query<(), With<DensityMap>>,
+        visible: Query<(&InheritedVisibility, &ViewVisibility), With<Handle<DitheredBuffer>>>,
+        mut diagnostics: Diagnostics,
+    ) {
+        let count = chunks.iter().count();
+        let visible_count = visible
+            .iter()
+            .filter(|(inherited_visibility, view_visibility)| {
+                inherited_visibility.get() && view_visibility.get()
+            })
+            .count();
+        diagnostics.add_measurement(&Self::CHUNK_COUNT, || count as f64);
+        diagnostics.add_measurement(&Self::VISIBLE_CHUNK_COUNT, || visible_count as f64);
+    }
+
+    /// Logs the running dither tasks and the duration of the computations finished since the last frame
+    fn measure_dithering(
+        tasks: Query<(), With<ComputeDither>>,
+        timings: Option<ResMut<DitherTimings>>,
+        mut diagnostics: Diagnostics,
+    ) {
+        let pending = tasks.iter().count();
+        diagnostics.add_measurement(&Self::PENDING_DITHER_TASKS, || pending as f64);
+
+        let Some(mut timings) = timings else {
+            return;
+        };
+        let finished = mem::take(&mut *timings);
+        // Only frames with finished computations are measured
+        if let Some(average) = finished.average() {
+            diagnostics
+                .add_measurement(&Self::DITHER_TIME_AVERAGE, || average.as_secs_f64() * 1000.);
+            diagnostics.add_measurement(&Self::DITHER_TIME_MAX, || {
+                finished.max.as_secs_f64() * 1000.
+            });
+        }
+    }
+
+    /// Logs the size of the blade positions and blade states in the main world
+    fn measure_instance_data(
+        dither: Res<Assets<DitheredBuffer>>,
+        blade_states: Query<&GrassBladeStates>,
+        mut diagnostics: Diagnostics,
+    ) {
+        let positions: usize = dither
+            .iter()
+            .map(|(_, buffer)| mem::size_of_val(buffer.positions.as_slice()))
+            .sum();
+        let states: usize = blade_states
+            .iter()
+            .map(|states| mem::size_of_val(states.states()))
+            .sum();
+        diagnostics.add_measurement(&Self::INSTANCE_DATA_BYTES, || (positions + states) as f64);
+    }
+
+    /// Logs the draw calls counted by the render world
+    fn measure_draw_calls(draw_calls: Option<Res<GrassDrawCalls>>, mut diagnostics: Diagnostics) {
+        let Some(draw_calls) = draw_calls else {
+            return;
+        };
+        diagnostics.add_measurement(&Self::DRAW_CALLS, || draw_calls.last_frame() as f64);
+    }
 }
@@ -0,0 +1,269 @@
+//! Contains the [`GrassDisplacer`] component, which pushes grass blades away from an entity.
+//!
+//! Optionally, a [`GrassTrail`] keeps the grass bent for a while after a displacer passed
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+/// The maximum amount of [`GrassDisplacer`]s that affect the grass at the same time.
+///
+/// If more displacers exist, the ones closest to the camera are used
+pub const MAX_DISPLACERS: usize = 16;
+
+/// Bends the grass blades around the entity away from it.
+///
+/// The position of the displacer is taken from its [`GlobalTransform`]
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct GrassDisplacer {
+    /// The radius in which blades are affected
+    pub radius: f32,
+    /// How far the blades are pushed away
+    pub strength: f32,
+    /// How fast the effect fades out towards the radius.
+    ///
+    /// 1 means a linear falloff, bigger values make the effect more local
+    pub falloff: f32,
+}
+impl Default for GrassDisplacer {
+    fn default() -> Self {
+        GrassDisplacer {
+            radius: 1.,
+            strength: 1.,
+            falloff: 1.,
+        }
+    }
+}
+/// A [resource](bevy::prelude::Resource) that records where [`GrassDisplacer`]s have been.
+///
+/// The trail is stored in a texture covering `area` on the x,z plane.
+/// Grass inside the area stays bent after a displacer passed and slowly rises again.
+/// If the resource doesn't exist, no trail is drawn
+#[derive(Resource)]
+pub struct GrassTrail {
+    /// The area covered by the trail in world space
+    pub area: Rect,
+    /// How much of the trail fades per second. A value of 0.5 means that the trail disappears after 2 seconds
+    pub decay: f32,
+    /// The texture storing the trail.
+    ///
+    /// The rg channels store the direction and the b channel the strength of the displacement
+    pub texture: Handle<Image>,
+}
+impl GrassTrail {
+    /// Creates a new trail and adds the texture to the assets
+    ///
+    /// `resolution` is the size of the texture in pixels per world unit
+    pub fn new(area: Rect, resolution: f32, decay: f32, images: &mut Assets<Image>) -> Self {
+        let size = (area.size() * resolution).ceil().max(Vec2::ONE).as_uvec2();
+        let image = Image::new_fill(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[127, 127, 0, 255],
+            TextureFormat::Rgba8Unorm,
+            RenderAssetUsages::default(),
+        );
+        GrassTrail {
+            area,
+            decay,
+            texture: images.add(image),
+        }
+    }
+}
+impl Clone for GrassTrail {
+    fn clone(&self) -> Self {
+        GrassTrail {
+            area: self.area,
+            decay: self.decay,
+            texture: self.texture.clone_weak(),
+        }
+    }
+}
+/// The intensity of the trail in full precision.
+///
+/// The texture only stores 8 bits per pixel, which is too coarse to fade the trail by a small amount each frame
+#[derive(Default)]
+pub(crate) struct TrailIntensity {
+    texture: AssetId<Image>,
+    values: Vec<f32>,
+}
+impl TrailIntensity {
+    /// Reads the intensity back from the texture if the trail texture changed
+    fn sync(&mut self, id: AssetId<Image>, image: &Image) {
+        let pixel_count = image.data.len() / 4;
+        if self.texture == id && self.values.len() == pixel_count {
+            return;
+        }
+        self.texture = id;
+        self.values = image
+            .data
+            .chunks_exact(4)
+            .map(|pixel| pixel[2] as f32 / 255.)
+            .collect();
+    }
+    fn decay(&mut self, amount: f32) {
+        for value in &mut self.values {
+            *value = (*value - amount).max(0.);
+        }
+    }
+}
+fn quantize(value: f32) -> u8 {
+    (value * 255.).round() as u8
+}
+/// Fades the trail and stamps all displacers into it
+///
+/// The texture is only touched if a pixel actually changes, since every mutable access re-uploads it
+pub(crate) fn update_grass_trail(
+    time: Res<Time>,
+    trail: Option<Res<GrassTrail>>,
+    displacers: Query<(&GrassDisplacer, &GlobalTransform)>,
+    mut images: ResMut<Assets<Image>>,
+    mut intensity: Local<TrailIntensity>,
+) {
+    let Some(trail) = trail else {
+        return;
+    };
+    let Some(image) = images.get(&trail.texture) else {
+        return;
+    };
+    let size = image.size();
+    intensity.sync(trail.texture.id(), image);
+    intensity.decay(trail.decay * time.delta_seconds());
+
+    // pixels which got a new direction from a displacer
+    let mut stamped = Vec::new();
+    let pixel_size = trail.area.size() / size.as_vec2();
+    for (displacer, transform) in &displacers {
+        let center = transform.translation().xz();
+        let min = ((center - displacer.radius - trail.area.min) / pixel_size)
+            .floor()
+            .max(Vec2::ZERO)
+            .as_uvec2();
+        let max = ((center + displacer.radius - trail.area.min) / pixel_size)
+            .ceil()
+            .as_uvec2()
+            .min(size);
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let position = trail.area.min + (Vec2::new(x as f32, y as f32) + 0.5) * pixel_size;
+                let offset = position - center;
+                let distance = offset.length();
+                if distance >= displacer.radius {
+                    continue;
+                }
+                let strength = (1. - distance / displacer.radius)
+                    .powf(displacer.falloff)
+                    .min(1.);
+                let index = (y * size.x + x) as usize;
+                if strength > intensity.values[index] {
+                    intensity.values[index] = strength;
+                    let direction = offset.normalize_or_zero() * 0.5 + 0.5;
+                    stamped.push((index, (direction * 255.).as_uvec2()));
+                }
+            }
+        }
+    }
+    let faded = image
+        .data
+        .chunks_exact(4)
+        .zip(&intensity.values)
+        .any(|(pixel, value)| pixel[2] != quantize(*value));
+    if stamped.is_empty() && !faded {
+        return;
+    }
+    let Some(image) = images.get_mut(&trail.texture) else {
+        return;
+    };
+    for (pixel, value) in image.data.chunks_exact_mut(4).zip(&intensity.values) {
+        pixel[2] = quantize(*value);
+    }
+    for (index, direction) in stamped {
+        let pixel = &mut image.data[index * 4..index * 4 + 4];
+        pixel[0] = direction.x as u8;
+        pixel[1] = direction.y as u8;
+    }
+}
+
+/// A [`GrassDisplacer`] as seen by the shader
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ShaderDisplacer {
+    /// xyz: the world position, w: the radius
+    position_radius: Vec4,
+    /// x: the strength, y: the falloff
+    strength_falloff: Vec4,
+}
+impl ShaderDisplacer {
+    pub fn new(displacer: &GrassDisplacer, position: Vec3) -> Self {
+        ShaderDisplacer {
+            position_radius: position.extend(displacer.radius),
+            strength_falloff: Vec4::new(displacer.strength, displacer.falloff, 0., 0.),
+        }
+    }
+}
+/// All displacers affecting the grass in the current frame.
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ShaderDisplacers {
+    /// The area covered by the trail. xy: the minimum, zw: the size
+    trail_area: Vec4,
+    count: u32,
+    /// 1 if a trail exists
+    trail_enabled: u32,
+    /// Wasm requires shader uniforms to be aligned to 16 bytes
+    _wasm_padding: [u32; 2],
+    displacers: [ShaderDisplacer; MAX_DISPLACERS],
+}
+impl ShaderDisplacers {
+    pub fn new(displacers: &[ShaderDisplacer], trail_area: Option<Rect>) -> Self {
+        let count = displacers.len().min(MAX_DISPLACERS);
+        let mut shader_displacers = [ShaderDisplacer::default(); MAX_DISPLACERS];
+        shader_displacers[..count].copy_from_slice(&displacers[..count]);
+        ShaderDisplacers {
+            trail_area: trail_area.map_or(Vec4::ZERO, |area| {
+                Vec4::new(area.min.x, area.min.y, area.width(), area.height())
+            }),
+            count: count as u32,
+            trail_enabled: trail_area.is_some() as u32,
+            _wasm_padding: [0; 2],
+            displacers: shader_displacers,
+        }
+    }
+}
+/// The displacers of the current frame in the render world
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedDisplacers(pub Vec<ShaderDisplacer>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trail_decay_is_independent_of_the_frame_rate() {
+        let mut slow = TrailIntensity {
+            values: vec![1.],
+            ..default()
+        };
+        let mut fast = TrailIntensity {
+            values: vec![1.],
+            ..default()
+        };
+        for _ in 0..30 {
+            slow.decay(0.1 / 30.);
+        }
+        for _ in 0..144 {
+            fast.decay(0.1 / 144.);
+        }
+        assert!((slow.values[0] - 0.9).abs() < 1e-4);
+        assert!((fast.values[0] - 0.9).abs() < 1e-4);
+    }
+}
@@ -1,7 +1,8 @@
 use std::error::Error;
 use std::fmt::Display;
+use std::ops::Range;
 
-use bevy::asset::Asset;
+use bevy::asset::{Asset, LoadState};
 use bevy::ecs::system::lifetimeless::SRes;
 use bevy::ecs::system::{CommandQueue, SystemParamItem};
 use bevy::math::Vec3Swizzles;
@@ -12,8 +13,11 @@ use bevy::render::render_resource::{Buffer, BufferInitDescriptor, BufferUsages};
 use bevy::render::renderer::RenderDevice;
 use bevy::tasks::futures_lite::future;
 use bevy::tasks::{block_on, AsyncComputeTaskPool, Task};
-use image::DynamicImage;
+use bevy::utils::HashMap;
+use bevy::utils::{Duration, Instant};
+use image::{DynamicImage, GrayImage};
 
+use crate::bake::GrassBake;
 use crate::map::DensityMap;
 
 // see https://surma.dev/things/ditherpunk/ for a good resource regarding ordered dithering
@@ -28,6 +32,10 @@ const BAYER_DITHER: [[u8; 8]; 8] = [
     [61, 31, 55, 23, 61, 29, 53, 21],
 ];
 const MIN_AREA: f32 = 0.0001;
+/// The amount of cells per side, the blades of a chunk are grouped in.
+///
+/// Each cell selects its own [`GrassLod`](crate::prelude::GrassLod)
+const CELLS_PER_SIDE: usize = 8;
 #[derive(PartialEq, Debug)]
 pub enum DitherComputeError {
     ImageFormat,
@@ -66,23 +74,140 @@ pub(crate) fn dither_density_map(
     if area < MIN_AREA {
         return Err(DitherComputeError::ChunkAreaToSmall(area));
     }
-    let image_length = (image.size().length_squared() as f32).sqrt();
     let Ok(dynamic_image) = image.try_into_dynamic() else {
         return Err(DitherComputeError::ImageFormat);
     };
-    // Capacity is not precise but should be a good estimate
-
-    let mut dither_buffer = Vec::with_capacity(image_length as usize);
     if !matches!(dynamic_image, DynamicImage::ImageLuma8(_)) {
         warn_once!("The density map is prefered to be in Luma8(/R8) encoding");
     }
     // This conversion doesn't cost anything if the image is already luma8
     // but makes up for most of the function duration otherwise.
     let buffer = dynamic_image.into_luma8();
+    // Capacity is not precise but should be a good estimate
+    let mut dither_buffer = Vec::with_capacity(buffer.width().max(buffer.height()) as usize);
+    dither_luma(
+        &buffer,
+        density,
+        field_size,
+        0..usize::MAX,
+        0..usize::MAX,
+        |position| dither_buffer.push(position),
+    );
+    Ok(group_into_cells(dither_buffer, field_size))
+}
+/// Dithers the density map again, but only inside of the cells touched by `region`.
+///
+/// The blades of all other cells are copied from `buffer`.
+/// The result is equal to dithering the whole density map again
+pub(crate) fn dither_region(
+    buffer: &DitheredBuffer,
+    image: Image,
+    density: f32,
+    field_size: Vec2,
+    region: Rect,
+) -> Result<DitheredBuffer, DitherComputeError> {
+    if density < 0. {
+        return Err(DitherComputeError::DensityToSmall(density));
+    }
+    let area = field_size.x * field_size.y;
+    if area < MIN_AREA {
+        return Err(DitherComputeError::ChunkAreaToSmall(area));
+    }
+    let Ok(dynamic_image) = image.try_into_dynamic() else {
+        return Err(DitherComputeError::ImageFormat);
+    };
+    let luma = dynamic_image.into_luma8();
+
+    let mut cells = vec![Vec::new(); CELLS_PER_SIDE * CELLS_PER_SIDE];
+    for cell in &buffer.cells {
+        cells[cell_index(cell.center, field_size)] =
+            buffer.positions[cell.instances.start as usize..cell.instances.end as usize].to_vec();
+    }
+    let cell_size = field_size / CELLS_PER_SIDE as f32;
+    let last_cell = UVec2::splat(CELLS_PER_SIDE as u32 - 1);
+    let min_cell = (region.min.max(Vec2::ZERO) / cell_size)
+        .as_uvec2()
+        .min(last_cell);
+    let max_cell = (region.max.max(Vec2::ZERO) / cell_size)
+        .as_uvec2()
+        .min(last_cell);
+    let touched = |index: usize| {
+        let cell = UVec2::new(
+            (index % CELLS_PER_SIDE) as u32,
+            (index / CELLS_PER_SIDE) as u32,
+        );
+        cell.cmpge(min_cell).all() && cell.cmple(max_cell).all()
+    };
+    for (index, cell) in cells.iter_mut().enumerate() {
+        if touched(index) {
+            cell.clear();
+        }
+    }
+    // the indices of the blades in the touched cells, with a margin for rounding errors
+    let counts = (density * field_size).abs();
+    let index_range = |min: u32, max: u32, count: f32| {
+        let start = (min as f32 / CELLS_PER_SIDE as f32 * count) as usize;
+        let end = ((max + 1) as f32 / CELLS_PER_SIDE as f32 * count).ceil() as usize;
+        start.saturating_sub(1)..end + 1
+    };
+    dither_luma(
+        &luma,
+        density,
+        field_size,
+        index_range(min_cell.x, max_cell.x, counts.x),
+        index_range(min_cell.y, max_cell.y, counts.y),
+        |position| {
+            let index = cell_index(position, field_size);
+            if touched(index) {
+                cells[index].push(position);
+            }
+        },
+    );
+    Ok(from_cells(cells, field_size))
+}
+/// Dithers the touched regions of the density maps of the chunks again.
+///
+/// The regions are given relative to the size of the density maps
+pub(crate) fn dither_touched_regions<'a>(
+    chunks: impl IntoIterator<Item = (&'a Aabb, &'a DensityMap, &'a Handle<DitheredBuffer>)>,
+    touched: &HashMap<AssetId<Image>, Rect>,
+    images: &Assets<Image>,
+    dithered: &mut Assets<DitheredBuffer>,
+) {
+    for (aabb, density_map, handle) in chunks {
+        let Some(region) = touched.get(&density_map.density_map.id()) else {
+            continue;
+        };
+        let (Some(image), Some(buffer)) =
+            (images.get(&density_map.density_map), dithered.get(handle))
+        else {
+            continue;
+        };
+        let size = aabb.half_extents.xz() * 2.;
+        let region = Rect::from_corners(region.min * size, region.max * size);
+        match dither_region(buffer, image.clone(), density_map.density, size, region) {
+            Ok(buffer) => {
+                if let Some(dithered) = dithered.get_mut(handle) {
+                    *dithered = buffer;
+                }
+            }
+            Err(error) => warn_once!("Failed to dither the changed density map: {error}"),
+        }
+    }
+}
+/// Dithers the pixels of a luma8 density map in the given ranges of blade indices
+fn dither_luma(
+    buffer: &GrayImage,
+    density: f32,
+    field_size: Vec2,
+    i_range: Range<usize>,
+    j_range: Range<usize>,
+    mut push: impl FnMut(Vec2),
+) {
     let i_count = (density * field_size.x).abs() as usize;
     let j_count = (density * field_size.y).abs() as usize;
-    for i in 0..i_count {
-        for j in 0..j_count {
+    for i in i_range.start..i_range.end.min(i_count) {
+        for j in j_range.start..j_range.end.min(j_count) {
             let threshold = BAYER_DITHER[i % 8][j % 8];
 
             //normalize i,j between 0,1
@@ -94,28 +219,96 @@ pub(crate) fn dither_density_map(
 
             let pixel = buffer.get_pixel(x as u32, y as u32).0[0];
             if pixel > threshold * 4 {
-                dither_buffer.push(Vec2::new(i * field_size.x, j * field_size.y));
+                push(Vec2::new(i * field_size.x, j * field_size.y));
             }
         }
     }
-    Ok(DitheredBuffer {
-        positions: dither_buffer,
-    })
+}
+/// The index of the cell containing the position
+fn cell_index(position: Vec2, field_size: Vec2) -> usize {
+    let cell_size = field_size / CELLS_PER_SIDE as f32;
+    let cell = (position / cell_size)
+        .as_uvec2()
+        .min(UVec2::splat(CELLS_PER_SIDE as u32 - 1));
+    cell.y as usize * CELLS_PER_SIDE + cell.x as usize
+}
+/// Sorts the blades by the cell they are in, so that each cell covers a continuous range of instances
+fn group_into_cells(positions: Vec<Vec2>, field_size: Vec2) -> DitheredBuffer {
+    let mut cells = vec![Vec::new(); CELLS_PER_SIDE * CELLS_PER_SIDE];
+    for position in positions {
+        cells[cell_index(position, field_size)].push(position);
+    }
+    from_cells(cells, field_size)
+}
+/// Creates the buffer from the blades of each cell
+fn from_cells(cells: Vec<Vec<Vec2>>, field_size: Vec2) -> DitheredBuffer {
+    let cell_size = field_size / CELLS_PER_SIDE as f32;
+    let mut buffer = DitheredBuffer {
+        positions: Vec::with_capacity(cells.iter().map(Vec::len).sum()),
+        cells: Vec::new(),
+    };
+    for (index, cell) in cells.into_iter().enumerate() {
+        if cell.is_empty() {
+            continue;
+        }
+        let start = buffer.positions.len() as u32;
+        buffer.positions.extend(cell);
+        let coordinate = Vec2::new(
+            (index % CELLS_PER_SIDE) as f32,
+            (index / CELLS_PER_SIDE) as f32,
+        );
+        buffer.cells.push(DitherCell {
+            center: (coordinate + 0.5) * cell_size,
+            instances: start..buffer.positions.len() as u32,
+        });
+    }
+    buffer
 }
 #[derive(Component)]
 pub(crate) struct ComputeDither(Task<CommandQueue>);
+
+/// The time spent on dithering since the timings were last taken
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DitherTimings {
+    /// The amount of finished computations
+    pub computations: u32,
+    pub total: Duration,
+    pub max: Duration,
+}
+impl DitherTimings {
+    pub fn record(&mut self, duration: Duration) {
+        self.computations += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+    /// The average duration of a computation, if any computation finished
+    pub fn average(&self) -> Option<Duration> {
+        (self.computations > 0).then(|| self.total / self.computations)
+    }
+}
 /// A buffer containing the dithered density map
 ///
 /// This struct shouldn't be modified by the user
 #[derive(Clone, Debug, TypePath, Asset, PartialEq)]
 pub(crate) struct DitheredBuffer {
     pub positions: Vec<Vec2>,
+    /// The non-empty cells of the chunk
+    pub cells: Vec<DitherCell>,
+}
+/// A group of blades close to each other
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DitherCell {
+    /// The center of the cell on the x,z plane of the chunk
+    pub center: Vec2,
+    /// The range of the blades of the cell in the [`DitheredBuffer`]
+    pub instances: Range<u32>,
 }
 /// The gpu representation of a [`DitheredBuffer`]
 #[derive(Debug)]
 pub(crate) struct GpuDitheredBuffer {
     pub buffer: Buffer,
     pub instances: usize,
+    pub cells: Vec<DitherCell>,
 }
 impl RenderAsset for DitheredBuffer {
     type PreparedAsset = GpuDitheredBuffer;
@@ -139,14 +332,30 @@ impl RenderAsset for DitheredBuffer {
         Ok(GpuDitheredBuffer {
             buffer,
             instances: self.positions.len(),
+            cells: self.cells,
         })
     }
 }
+#[allow(clippy::type_complexity)]
+/// A chunk waiting for its density map or [`GrassBake`] to load
+type PendingChunk = (Entity, DensityMap, Aabb, Option<Handle<GrassBake>>);
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub(crate) fn add_dither_task(
     mut commands: Commands,
-    grasses: Query<(Entity, &DensityMap, &Aabb), Or<(Changed<DensityMap>, Changed<Aabb>)>>,
+    grasses: Query<
+        (Entity, &DensityMap, &Aabb, Option<&Handle<GrassBake>>),
+        Or<(
+            Changed<DensityMap>,
+            Changed<Aabb>,
+            Changed<Handle<GrassBake>>,
+        )>,
+    >,
     images: Res<Assets<Image>>,
-    mut storage: Local<Vec<(Entity, DensityMap, Aabb)>>,
+    bakes: Res<Assets<GrassBake>>,
+    asset_server: Res<AssetServer>,
+    mut dithered: ResMut<Assets<DitheredBuffer>>,
+    mut storage: Local<Vec<PendingChunk>>,
     mut event_writer: EventWriter<GrassComputeEvent>,
 ) {
     if storage.is_empty() && grasses.is_empty() {
@@ -155,18 +364,55 @@ pub(crate) fn add_dither_task(
     let stored = std::mem::take(&mut *storage);
     let thread_pool: &AsyncComputeTaskPool = AsyncComputeTaskPool::get();
     let mut data = Vec::new();
-    for (e, density_map, aabb) in grasses
+    for (e, density_map, aabb, bake) in grasses
         .iter()
-        .chain(stored.iter().map(|(e, map, aabb)| (*e, map, aabb)))
+        .map(|(e, map, aabb, bake)| (e, map.clone(), *aabb, bake.cloned()))
+        .chain(stored)
     {
         let Some(image) = images.get(&density_map.density_map) else {
             // the entity might have been deleted from the world
             if commands.get_entity(e).is_some() {
-                storage.push((e, density_map.clone(), *aabb));
+                storage.push((e, density_map, aabb, bake));
             }
             continue;
         };
-        data.push((e, image.clone(), density_map.density, *aabb));
+        if let Some(handle) = bake {
+            match bakes.get(&handle) {
+                Some(bake) if bake.matches(image, density_map.density, &aabb) => {
+                    // the baked blades replace the dithering
+                    let Some(mut entity) = commands.get_entity(e) else {
+                        continue;
+                    };
+                    entity
+                        .remove::<ComputeDither>()
+                        .insert(dithered.add(bake.buffer.clone()));
+                    event_writer.send(GrassComputeEvent::StartComputation(e));
+                    event_writer.send(GrassComputeEvent::FinishedComputation(e));
+                    continue;
+                }
+                Some(_) => {
+                    warn!("The grass bake of chunk {e:?} is stale, the density map is dithered instead");
+                }
+                None if asset_server.load_state(&handle) == LoadState::Failed => {
+                    warn!("The grass bake of chunk {e:?} failed to load, the density map is dithered instead");
+                }
+                // the handle isn't loaded by the asset server, so the bake never arrives
+                None if matches!(
+                    asset_server.get_load_state(&handle),
+                    None | Some(LoadState::NotLoaded)
+                ) =>
+                {
+                    warn!("The grass bake of chunk {e:?} doesn't exist and isn't loading, the density map is dithered instead");
+                }
+                None => {
+                    if commands.get_entity(e).is_some() {
+                        storage.push((e, density_map, aabb, Some(handle)));
+                    }
+                    continue;
+                }
+            }
+        }
+        data.push((e, image.clone(), density_map.density, aabb));
     }
     for (e, map, density, aabb) in data.into_iter() {
         event_writer.send(GrassComputeEvent::StartComputation(e));
@@ -180,7 +426,15 @@ pub(crate) fn add_dither_task(
                     entity_builder.remove::<ComputeDither>();
                 }
             });
-            match dither_density_map(map, density, xz) {
+            let start = Instant::now();
+            let result = dither_density_map(map, density, xz);
+            let duration = start.elapsed();
+            command_queue.push(move |world: &mut World| {
+                if let Some(mut timings) = world.get_resource_mut::<DitherTimings>() {
+                    timings.record(duration);
+                }
+            });
+            match result {
                 Ok(buffer) => {
                     command_queue.push(move |world: &mut World| {
                         let event = on_dither_success(world, e, buffer);
@@ -207,9 +461,9 @@ fn on_dither_success(world: &mut World, e: Entity, buffer: DitheredBuffer) -> Gr
     let handle = dithered.add(buffer);
     if let Some(mut entity_builder) = world.get_entity_mut(e) {
         entity_builder.insert(handle);
-        return GrassComputeEvent::FinishedComputation(e);
+        GrassComputeEvent::FinishedComputation(e)
     } else {
-        return GrassComputeError::EntityDoesNotExist(e).into();
+        GrassComputeError::EntityDoesNotExist(e).into()
     }
 }
 
@@ -296,6 +550,22 @@ mod tests {
         assert!(dither.unwrap().positions.is_empty());
     }
     #[test]
+    fn blades_are_grouped_into_cells() {
+        let image = Image::default(); // 1x1x1 image all white
+        let dither = super::dither_density_map(image, 2., Vec2::new(16., 8.)).unwrap();
+        assert_eq!(dither.cells.len(), 64);
+        let mut end = 0;
+        for cell in &dither.cells {
+            assert_eq!(cell.instances.start, end);
+            end = cell.instances.end;
+            let half_cell = Vec2::new(1., 0.5);
+            for position in &dither.positions[cell.instances.start as usize..end as usize] {
+                assert!((*position - cell.center).abs().cmple(half_cell).all());
+            }
+        }
+        assert_eq!(end as usize, dither.positions.len());
+    }
+    #[test]
     fn wrong_input() {
         let image = Image::default(); // 1x1x1 image all white
                                       // density=0 should return 0 results but still work
@@ -331,4 +601,51 @@ mod tests {
         let dither = super::dither_density_map(image.clone(), -0.1, Vec2::new(10., 10.));
         assert_eq!(dither, Err(DitherComputeError::DensityToSmall(-0.1)));
     }
+    #[test]
+    fn dither_timings() {
+        use bevy::utils::Duration;
+
+        let mut timings = super::DitherTimings::default();
+        assert_eq!(timings.average(), None);
+        timings.record(Duration::from_millis(10));
+        timings.record(Duration::from_millis(30));
+        assert_eq!(timings.computations, 2);
+        assert_eq!(timings.average(), Some(Duration::from_millis(20)));
+        assert_eq!(timings.max, Duration::from_millis(30));
+    }
+    #[test]
+    fn dither_region_matches_full_dither() {
+        use bevy::math::Rect;
+        use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+        let size = Extent3d {
+            width: 16,
+            height: 16,
+            depth_or_array_layers: 1,
+        };
+        let image = |data: Vec<u8>| {
+            Image::new(
+                size,
+                TextureDimension::D2,
+                data,
+                TextureFormat::R8Unorm,
+                RenderAssetUsages::default(),
+            )
+        };
+        let field_size = Vec2::new(20., 10.);
+        let mut data = vec![100; 16 * 16];
+        let before = super::dither_density_map(image(data.clone()), 3., field_size).unwrap();
+        // paint a dense spot into the map
+        for y in 4..8 {
+            for x in 2..6 {
+                data[y * 16 + x] = 255;
+            }
+        }
+        let region = Rect::new(2.5, 2.5, 7.5, 5.);
+        let partial =
+            super::dither_region(&before, image(data.clone()), 3., field_size, region).unwrap();
+        let full = super::dither_density_map(image(data), 3., field_size).unwrap();
+        assert_ne!(before, full);
+        assert_eq!(partial, full);
+    }
 }
@@ -0,0 +1,815 @@
+//! Contains the [`GrassEditorPlugin`], which allows painting the maps of grass chunks in game.
+//!
+//! Requires the `editor` feature of the crate.
+//! The painted maps are only changed in memory, they are not saved to disk
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{primitives::Aabb, render_resource::TextureFormat},
+    utils::{HashMap, HashSet},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    dithering::{dither_touched_regions, ComputeDither, DitheredBuffer},
+    history::{GrassEditHistory, GrassEditHistoryPlugin},
+    map::{ColorMap, DensityMap, YMap},
+    prelude::WarblerHeight,
+};
+
+/// A [`Plugin`] to paint the maps of grass chunks with the mouse.
+///
+/// The cursor is projected onto the terrain described by the [`YMap`] of the chunks.
+/// While the paint button of the [`GrassEditor`] is pressed, the brush paints into the map selected by the [`PaintTarget`].
+/// Painting the [`DensityMap`] only dithers the blades of the touched area again.
+///
+/// Each stroke is recorded in the [`GrassEditHistory`], so it can be undone with a [`GrassHistoryCommand`](crate::history::GrassHistoryCommand).
+/// The [`GrassEditHistoryPlugin`] is added if it isn't already.
+///
+/// Should be added after the [`WarblersPlugin`](crate::prelude::WarblersPlugin)
+/// # Example
+/// ```rust
+/// use bevy::prelude::App;
+/// use warbler_grass::editor::{GrassEditor, GrassEditorPlugin, PaintTarget};
+///
+/// App::new()
+///     .add_plugins(GrassEditorPlugin)
+///     // paint the y-map instead of the density map
+///     .insert_resource(GrassEditor {
+///         target: PaintTarget::YMap,
+///         ..Default::default()
+///     });
+/// ```
+pub struct GrassEditorPlugin;
+impl Plugin for GrassEditorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<GrassEditHistoryPlugin>() {
+            app.add_plugins(GrassEditHistoryPlugin);
+        }
+        app.init_resource::<GrassEditor>()
+            .init_resource::<GrassEditorCursor>()
+            .add_systems(
+                Update,
+                (update_cursor, paint_chunks, draw_brush)
+                    .chain()
+                    .in_set(GrassEditorSet),
+            );
+    }
+}
+
+/// The [`SystemSet`] of the systems of the [`GrassEditorPlugin`]
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GrassEditorSet;
+
+/// The settings of the grass editor
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct GrassEditor {
+    /// Whether the cursor is projected and the brush paints
+    pub enabled: bool,
+    /// The map the brush paints into
+    pub target: PaintTarget,
+    pub brush: GrassBrush,
+    /// The mouse button painting while pressed
+    pub paint_button: MouseButton,
+    /// The seconds between dithering the painted area of the [`DensityMap`] again.
+    ///
+    /// Dithering is expensive for big maps, so the painted area is collected while painting.
+    /// The area is always dithered once the stroke ends
+    pub dither_interval: f32,
+}
+impl Default for GrassEditor {
+    fn default() -> Self {
+        GrassEditor {
+            enabled: true,
+            target: PaintTarget::Density,
+            brush: GrassBrush::default(),
+            paint_button: MouseButton::Left,
+            dither_interval: 0.1,
+        }
+    }
+}
+
+/// The map of a chunk the [`GrassEditor`] paints into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaintTarget {
+    /// Paints the [`DensityMap`]. The blades of the painted area are dithered again
+    Density,
+    /// Paints the [`YMap`], which raises or lowers the terrain
+    YMap,
+    /// Paints the height texture of chunks with a [`WarblerHeight::Texture`]
+    Height,
+    /// Paints the given color into the [`ColorMap`] of a chunk.
+    ///
+    /// [`BrushMode::Subtract`] paints white, which removes the tint
+    Color(Color),
+}
+
+/// How the brush changes the painted map
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BrushMode {
+    /// Increases the values of the map
+    #[default]
+    Add,
+    /// Decreases the values of the map
+    Subtract,
+    /// Blends the values of the map with their neighbours
+    Smooth,
+}
+
+/// The shape and strength of the brush of the [`GrassEditor`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrassBrush {
+    /// The radius of the brush in the local space of the chunks
+    pub radius: f32,
+    /// The part of the radius over which the brush fades out.
+    ///
+    /// A falloff of 0 gives the brush a hard edge, a falloff of 1 fades it out from the center
+    pub falloff: f32,
+    /// How much the map changes per second in the center of the brush
+    pub strength: f32,
+    pub mode: BrushMode,
+}
+impl Default for GrassBrush {
+    fn default() -> Self {
+        GrassBrush {
+            radius: 5.,
+            falloff: 0.5,
+            strength: 1.,
+            mode: BrushMode::Add,
+        }
+    }
+}
+impl GrassBrush {
+    /// The influence of the brush at a distance from its center, between 0 and 1
+    pub fn weight(&self, distance: f32) -> f32 {
+        if distance >= self.radius {
+            return 0.;
+        }
+        let inner = self.radius * (1. - self.falloff.clamp(0., 1.));
+        if distance <= inner {
+            return 1.;
+        }
+        let t = (distance - inner) / (self.radius - inner);
+        // smoothstep from the inner radius to the edge
+        1. - t * t * (3. - 2. * t)
+    }
+}
+
+/// Marks the camera used by the [`GrassEditorPlugin`].
+///
+/// If no camera is marked, the active camera with the highest order is used
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct GrassEditorCamera;
+
+/// The point of the terrain under the cursor, updated every frame by the [`GrassEditorPlugin`]
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct GrassEditorCursor(pub Option<GrassEditorHit>);
+
+/// A point on the terrain of a chunk
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrassEditorHit {
+    /// The chunk under the cursor
+    pub chunk: Entity,
+    /// The position in world space
+    pub position: Vec3,
+}
+
+/// Projects the cursor onto the terrain of the chunks
+#[allow(clippy::type_complexity)]
+fn update_cursor(
+    editor: Res<GrassEditor>,
+    mut cursor: ResMut<GrassEditorCursor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, Has<GrassEditorCamera>)>,
+    chunks: Query<(Entity, &GlobalTransform, &Aabb, &YMap)>,
+    images: Res<Assets<Image>>,
+) {
+    cursor.0 = None;
+    if !editor.enabled {
+        return;
+    }
+    let Some(position) = windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let Some((camera, camera_transform, _)) = cameras
+        .iter()
+        .filter(|(camera, _, _)| camera.is_active)
+        .max_by_key(|(camera, _, marked)| (*marked, camera.order))
+    else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, position) else {
+        return;
+    };
+    let mut closest: Option<(f32, GrassEditorHit)> = None;
+    for (chunk, transform, aabb, y_map) in &chunks {
+        let Some(y_map) = images.get(&y_map.y_map) else {
+            continue;
+        };
+        let Some(position) = raycast_terrain(ray, transform, aabb, y_map) else {
+            continue;
+        };
+        let distance = position.distance(ray.origin);
+        if matches!(closest, Some((closest, _)) if closest <= distance) {
+            continue;
+        }
+        closest = Some((distance, GrassEditorHit { chunk, position }));
+    }
+    cursor.0 = closest.map(|(_, hit)| hit);
+}
+
+/// Intersects the ray with the terrain of the chunk, returning the hit in world space
+fn raycast_terrain(
+    ray: Ray3d,
+    transform: &GlobalTransform,
+    aabb: &Aabb,
+    y_map: &Image,
+) -> Option<Vec3> {
+    const STEPS: u32 = 128;
+    const REFINEMENTS: u32 = 10;
+    let to_local = transform.affine().inverse();
+    let origin = to_local.transform_point3(ray.origin);
+    let direction = to_local.transform_vector3(*ray.direction);
+    // the part of the ray inside of the aabb
+    let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+    let t_min = (min - origin) / direction;
+    let t_max = (max - origin) / direction;
+    let enter = t_min.min(t_max).max_element().max(0.);
+    let exit = t_min.max(t_max).min_element();
+    if enter.is_nan() || exit.is_nan() || enter >= exit {
+        return None;
+    }
+    let size = Vec3::from(aabb.half_extents) * 2.;
+    let above_terrain = |t: f32| {
+        let point = origin + direction * t;
+        let height = read_pixel(y_map, point.xz(), size.xz()).map_or(0., |pixel| pixel[0]);
+        point.y > height * size.y
+    };
+    if !above_terrain(enter) {
+        return None;
+    }
+    let step = (exit - enter) / STEPS as f32;
+    let mut above = enter;
+    for i in 1..=STEPS {
+        let t = enter + step * i as f32;
+        if above_terrain(t) {
+            above = t;
+            continue;
+        }
+        // refine the hit between the last point above and the first point below the terrain
+        let mut below = t;
+        for _ in 0..REFINEMENTS {
+            let middle = (above + below) / 2.;
+            if above_terrain(middle) {
+                above = middle;
+            } else {
+                below = middle;
+            }
+        }
+        return Some(transform.transform_point(origin + direction * below));
+    }
+    None
+}
+
+/// Paints into the maps of all chunks touched by the brush
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn paint_chunks(
+    editor: Res<GrassEditor>,
+    cursor: Res<GrassEditorCursor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    mut dithered: ResMut<Assets<DitheredBuffer>>,
+    mut history: ResMut<GrassEditHistory>,
+    chunks: Query<(
+        Entity,
+        &GlobalTransform,
+        &Aabb,
+        &DensityMap,
+        &YMap,
+        &WarblerHeight,
+        Option<&ColorMap>,
+        Option<&Handle<DitheredBuffer>>,
+        Has<ComputeDither>,
+    )>,
+    mut strokes: Local<HashMap<AssetId<Image>, PaintStroke>>,
+    mut pending: Local<PendingDither>,
+) {
+    let painting = mouse.pressed(editor.paint_button);
+    if !painting {
+        // a stroke ends when the button is released
+        if history.is_editing() {
+            history.finish_edit(&images);
+        }
+        strokes.clear();
+    }
+    pending.elapsed += time.delta_seconds();
+    if let Some(hit) = cursor.0.filter(|_| painting) {
+        let amount = editor.brush.strength * time.delta_seconds();
+        // chunks can share their maps, which are only painted once.
+        // The painted regions are stored relative to the size of the map
+        let mut painted: HashSet<AssetId<Image>> = HashSet::new();
+        for (chunk, transform, aabb, density_map, y_map, height, color_map, _, _) in &chunks {
+            let image = match editor.target {
+                PaintTarget::Density => &density_map.density_map,
+                PaintTarget::YMap => &y_map.y_map,
+                PaintTarget::Height => match height {
+                    WarblerHeight::Texture(texture) => texture,
+                    WarblerHeight::Uniform(_) => continue,
+                },
+                PaintTarget::Color(_) => match color_map {
+                    Some(color_map) => &color_map.color_map,
+                    None => continue,
+                },
+            };
+            if !painted.insert(image.id()) {
+                continue;
+            }
+            let size = (Vec3::from(aabb.half_extents) * 2.).xz();
+            let center = transform
+                .affine()
+                .inverse()
+                .transform_point3(hit.position)
+                .xz();
+            let Some(image_data) = images.get_mut(image) else {
+                continue;
+            };
+            history.track(chunk, image, image_data);
+            let paint = match editor.target {
+                PaintTarget::Color(color) => Paint::Color(color),
+                _ => Paint::Value,
+            };
+            let stroke = strokes.entry(image.id()).or_default();
+            let Some(region) = paint_image(
+                image_data,
+                stroke,
+                size,
+                center,
+                &editor.brush,
+                amount,
+                paint,
+            ) else {
+                continue;
+            };
+            if editor.target == PaintTarget::Density {
+                let region = Rect::from_corners(region.min / size, region.max / size);
+                pending
+                    .touched
+                    .entry(image.id())
+                    .and_modify(|touched| *touched = touched.union(region))
+                    .or_insert(region);
+            }
+        }
+    }
+    // dithering is expensive for big maps, so the painted regions are collected
+    // and dithered again at an interval
+    if pending.touched.is_empty() || (painting && pending.elapsed < editor.dither_interval) {
+        return;
+    }
+    pending.elapsed = 0.;
+    let touched = std::mem::take(&mut pending.touched);
+    // only the touched area is dithered again
+    let chunks = chunks
+        .iter()
+        .filter(|(.., computing)| !computing)
+        .filter_map(|(_, _, aabb, density_map, .., handle, _)| Some((aabb, density_map, handle?)));
+    dither_touched_regions(chunks, &touched, &images, &mut dithered);
+}
+/// The painted regions of the density maps, which weren't dithered again yet
+#[derive(Default)]
+struct PendingDither {
+    /// The regions relative to the size of the density maps
+    touched: HashMap<AssetId<Image>, Rect>,
+    /// The seconds since the density maps were dithered the last time
+    elapsed: f32,
+}
+
+/// What is painted into a map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Paint {
+    /// A single value, stored in all color channels
+    Value,
+    Color(Color),
+}
+
+/// The layout of the pixels the editor can paint into
+struct PixelLayout {
+    channels: usize,
+    bgra: bool,
+    srgb: bool,
+    /// Whether the channels are stored as `f32` instead of normalized bytes.
+    ///
+    /// 16 bit maps loaded by the [`persistence`](crate::persistence) module use [`TextureFormat::R32Float`]
+    float: bool,
+}
+impl PixelLayout {
+    fn of(format: TextureFormat) -> Option<Self> {
+        let (channels, bgra, srgb, float) = match format {
+            TextureFormat::R8Unorm => (1, false, false, false),
+            TextureFormat::Rg8Unorm => (2, false, false, false),
+            TextureFormat::Rgba8Unorm => (4, false, false, false),
+            TextureFormat::Rgba8UnormSrgb => (4, false, true, false),
+            TextureFormat::Bgra8Unorm => (4, true, false, false),
+            TextureFormat::Bgra8UnormSrgb => (4, true, true, false),
+            TextureFormat::R32Float => (1, false, false, true),
+            _ => return None,
+        };
+        Some(PixelLayout {
+            channels,
+            bgra,
+            srgb,
+            float,
+        })
+    }
+    /// The size of a pixel in bytes
+    fn pixel_size(&self) -> usize {
+        if self.float {
+            self.channels * 4
+        } else {
+            self.channels
+        }
+    }
+    /// Reads a channel of the pixel starting at the byte offset
+    fn read(&self, data: &[u8], offset: usize, channel: usize) -> f32 {
+        if self.float {
+            let start = offset + channel * 4;
+            f32::from_ne_bytes(data[start..start + 4].try_into().unwrap())
+        } else {
+            data[offset + self.byte(channel)] as f32 / 255.
+        }
+    }
+    /// Writes a channel of the pixel starting at the byte offset
+    fn write(&self, data: &mut [u8], offset: usize, channel: usize, value: f32) {
+        if self.float {
+            let start = offset + channel * 4;
+            data[start..start + 4].copy_from_slice(&value.to_ne_bytes());
+        } else {
+            data[offset + self.byte(channel)] = quantize(value);
+        }
+    }
+    /// The value a channel has after it is written to the image
+    fn stored(&self, value: f32) -> f32 {
+        if self.float {
+            value
+        } else {
+            quantize(value) as f32 / 255.
+        }
+    }
+    /// The index of the byte of a channel in rgba order
+    fn byte(&self, channel: usize) -> usize {
+        match (self.bgra, channel) {
+            (true, 0) => 2,
+            (true, 2) => 0,
+            _ => channel,
+        }
+    }
+    /// The channels that hold the painted value. The alpha channel is never painted
+    fn color_channels(&self) -> usize {
+        self.channels.min(3)
+    }
+}
+
+/// Reads the pixel at a position in the chunk, the same way as the grass shader does.
+///
+/// The channels are in rgba order and between 0 and 1
+pub(crate) fn read_pixel(image: &Image, position: Vec2, chunk_size: Vec2) -> Option<[f32; 4]> {
+    let layout = PixelLayout::of(image.texture_descriptor.format)?;
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let texel = (position / chunk_size * Vec2::new(width as f32, height as f32)).abs();
+    let x = (texel.x as usize).min(width - 1);
+    let y = (texel.y as usize).min(height - 1);
+    Some(pixel_at(
+        image,
+        &layout,
+        (y * width + x) * layout.pixel_size(),
+    ))
+}
+fn pixel_at(image: &Image, layout: &PixelLayout, offset: usize) -> [f32; 4] {
+    let mut pixel = [0., 0., 0., 1.];
+    for (channel, value) in pixel.iter_mut().enumerate().take(layout.channels) {
+        *value = layout.read(&image.data, offset, channel);
+    }
+    pixel
+}
+fn quantize(value: f32) -> u8 {
+    (value * 255.).round() as u8
+}
+
+/// The pixels of an image painted by the current stroke in full precision.
+///
+/// A stroke changes the pixels by a small amount each frame, which would be lost by rounding them to 8 bits
+#[derive(Default)]
+pub(crate) struct PaintStroke {
+    /// The channels of each pixel in rgba order
+    pixels: Vec<[f32; 4]>,
+}
+impl PaintStroke {
+    /// The pixel at the index in full precision.
+    ///
+    /// If the image was changed outside of the stroke, for example by an undo, the pixel is read from the image again
+    fn pixel(&mut self, image: &Image, layout: &PixelLayout, index: usize) -> [f32; 4] {
+        let pixel_count = (image.width() * image.height()) as usize;
+        if self.pixels.len() != pixel_count {
+            self.pixels = (0..pixel_count)
+                .map(|index| pixel_at(image, layout, index * layout.pixel_size()))
+                .collect();
+        }
+        let offset = index * layout.pixel_size();
+        let stored = self.pixels[index];
+        let unchanged = (0..layout.channels).all(|channel| {
+            layout.read(&image.data, offset, channel) == layout.stored(stored[channel])
+        });
+        if !unchanged {
+            self.pixels[index] = pixel_at(image, layout, offset);
+        }
+        self.pixels[index]
+    }
+}
+
+/// Paints the brush into the image of a chunk with the given size.
+///
+/// The painted pixels are accumulated in the `stroke`, so even small amounts change the image over time.
+/// Returns the painted region in the local space of the chunk
+pub(crate) fn paint_image(
+    image: &mut Image,
+    stroke: &mut PaintStroke,
+    chunk_size: Vec2,
+    center: Vec2,
+    brush: &GrassBrush,
+    amount: f32,
+    paint: Paint,
+) -> Option<Rect> {
+    let Some(layout) = PixelLayout::of(image.texture_descriptor.format) else {
+        warn_once!(
+            "The grass editor can't paint into images with the format {:?}",
+            image.texture_descriptor.format
+        );
+        return None;
+    };
+    let dimensions = Vec2::new(image.width() as f32, image.height() as f32);
+    let pixel_size = chunk_size / dimensions;
+    let min = ((center - brush.radius) / pixel_size)
+        .floor()
+        .max(Vec2::ZERO)
+        .as_uvec2();
+    let max = ((center + brush.radius) / pixel_size)
+        .ceil()
+        .min(dimensions)
+        .as_uvec2();
+    if min.cmpge(max).any() {
+        return None;
+    }
+    let width = image.width() as usize;
+    let index = |x: u32, y: u32| y as usize * width + x as usize;
+    // copy the pixels before painting, smoothing averages over the neighbours
+    let source: HashMap<(u32, u32), [f32; 4]> = (min.y.saturating_sub(1)
+        ..(max.y + 1).min(image.height()))
+        .flat_map(|y| {
+            (min.x.saturating_sub(1)..(max.x + 1).min(image.width())).map(move |x| (x, y))
+        })
+        .map(|(x, y)| ((x, y), stroke.pixel(image, &layout, index(x, y))))
+        .collect();
+    let target = match paint {
+        Paint::Color(color) => {
+            let color = match (brush.mode, layout.srgb) {
+                (BrushMode::Subtract, _) => Color::WHITE,
+                (_, true) => color.as_rgba(),
+                (_, false) => color.as_rgba_linear(),
+            };
+            Some(color.rgba_to_vec4().to_array())
+        }
+        Paint::Value => None,
+    };
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            let position = (Vec2::new(x as f32, y as f32) + 0.5) * pixel_size;
+            let weight = brush.weight(position.distance(center)) * amount;
+            if weight <= 0. {
+                continue;
+            }
+            let weight = weight.min(1.);
+            let mut pixel = source[&(x, y)];
+            if brush.mode == BrushMode::Smooth {
+                let neighbours: Vec<[f32; 4]> = (y.saturating_sub(1)..=y + 1)
+                    .flat_map(|ny| (x.saturating_sub(1)..=x + 1).map(move |nx| (nx, ny)))
+                    .filter_map(|coordinate| source.get(&coordinate).copied())
+                    .collect();
+                for (channel, value) in pixel.iter_mut().enumerate().take(layout.color_channels()) {
+                    let average = neighbours.iter().map(|pixel| pixel[channel]).sum::<f32>()
+                        / neighbours.len() as f32;
+                    *value += (average - *value) * weight;
+                }
+            } else if let Some(target) = target {
+                for channel in 0..layout.color_channels() {
+                    pixel[channel] += (target[channel] - pixel[channel]) * weight;
+                }
+            } else {
+                let sign = if brush.mode == BrushMode::Add {
+                    1.
+                } else {
+                    -1.
+                };
+                for value in pixel.iter_mut().take(layout.color_channels()) {
+                    *value = (*value + sign * weight).clamp(0., 1.);
+                }
+            }
+            let index = index(x, y);
+            stroke.pixels[index] = pixel;
+            let offset = index * layout.pixel_size();
+            for (channel, value) in pixel.iter().enumerate().take(layout.channels) {
+                layout.write(&mut image.data, offset, channel, *value);
+            }
+        }
+    }
+    Some(Rect::from_corners(
+        min.as_vec2() * pixel_size,
+        max.as_vec2() * pixel_size,
+    ))
+}
+
+/// Draws the outline of the brush at the cursor
+fn draw_brush(mut gizmos: Gizmos, editor: Res<GrassEditor>, cursor: Res<GrassEditorCursor>) {
+    let Some(hit) = cursor.0 else {
+        return;
+    };
+    let color = match editor.brush.mode {
+        BrushMode::Add => Color::GREEN,
+        BrushMode::Subtract => Color::RED,
+        BrushMode::Smooth => Color::CYAN,
+    };
+    let brush = &editor.brush;
+    gizmos.circle(hit.position, Direction3d::Y, brush.radius, color);
+    let inner = brush.radius * (1. - brush.falloff.clamp(0., 1.));
+    if inner > 0. {
+        gizmos.circle(hit.position, Direction3d::Y, inner, color.with_a(0.5));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::Vec2,
+        prelude::{Color, Image},
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use super::{paint_image, read_pixel, BrushMode, GrassBrush, Paint, PaintStroke};
+
+    fn image(format: TextureFormat, pixel: &[u8]) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: 10,
+                height: 10,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixel,
+            format,
+            RenderAssetUsages::default(),
+        )
+    }
+    #[test]
+    fn brush_falloff() {
+        let brush = GrassBrush {
+            radius: 2.,
+            falloff: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(brush.weight(0.5), 1.);
+        assert_eq!(brush.weight(1.5), 0.5);
+        assert_eq!(brush.weight(2.), 0.);
+    }
+    #[test]
+    fn paint_values() {
+        let mut map = image(TextureFormat::R8Unorm, &[0]);
+        let brush = GrassBrush {
+            radius: 1.5,
+            falloff: 0.,
+            ..Default::default()
+        };
+        let region = paint_image(
+            &mut map,
+            &mut PaintStroke::default(),
+            Vec2::splat(10.),
+            Vec2::splat(5.),
+            &brush,
+            1.,
+            Paint::Value,
+        );
+        assert_eq!(region.unwrap().min, Vec2::splat(3.));
+        assert_eq!(map.data.iter().filter(|value| **value == 255).count(), 4);
+        assert_eq!(map.data.iter().filter(|value| **value == 0).count(), 96);
+
+        let brush = GrassBrush {
+            mode: BrushMode::Subtract,
+            ..brush
+        };
+        paint_image(
+            &mut map,
+            &mut PaintStroke::default(),
+            Vec2::splat(10.),
+            Vec2::splat(5.),
+            &brush,
+            1.,
+            Paint::Value,
+        );
+        assert!(map.data.iter().all(|value| *value == 0));
+    }
+    #[test]
+    fn paint_colors() {
+        let mut map = image(TextureFormat::Bgra8Unorm, &[255, 255, 255, 255]);
+        let brush = GrassBrush {
+            radius: 1.,
+            falloff: 0.,
+            ..Default::default()
+        };
+        let red = Paint::Color(Color::RED);
+        paint_image(
+            &mut map,
+            &mut PaintStroke::default(),
+            Vec2::splat(10.),
+            Vec2::splat(5.5),
+            &brush,
+            1.,
+            red,
+        );
+        let offset = (5 * 10 + 5) * 4;
+        assert_eq!(map.data[offset..offset + 4], [0, 0, 255, 255]);
+    }
+    #[test]
+    fn small_steps_reach_the_target() {
+        let brush = GrassBrush {
+            radius: 1.,
+            falloff: 0.,
+            ..Default::default()
+        };
+        let offset = 5 * 10 + 5;
+        // each step is far below one 8 bit step
+        let amount = 1. / 1000.;
+
+        let mut map = image(TextureFormat::R8Unorm, &[0]);
+        let mut stroke = PaintStroke::default();
+        for _ in 0..1100 {
+            paint_image(
+                &mut map,
+                &mut stroke,
+                Vec2::splat(10.),
+                Vec2::splat(5.5),
+                &brush,
+                amount,
+                Paint::Value,
+            );
+        }
+        assert_eq!(map.data[offset], 255);
+
+        let mut map = image(TextureFormat::Rgba8Unorm, &[255, 255, 255, 255]);
+        let mut stroke = PaintStroke::default();
+        for _ in 0..10000 {
+            paint_image(
+                &mut map,
+                &mut stroke,
+                Vec2::splat(10.),
+                Vec2::splat(5.5),
+                &brush,
+                amount,
+                Paint::Color(Color::RED),
+            );
+        }
+        assert_eq!(map.data[offset * 4..offset * 4 + 4], [255, 0, 0, 255]);
+    }
+    #[test]
+    fn paint_float_maps() {
+        let mut y_map = image(TextureFormat::R32Float, &0.2f32.to_ne_bytes());
+        let chunk_size = Vec2::splat(10.);
+        let center = Vec2::splat(5.5);
+        let height = read_pixel(&y_map, center, chunk_size).unwrap()[0];
+        assert!((height - 0.2).abs() < 1e-4);
+
+        let brush = GrassBrush {
+            radius: 1.,
+            falloff: 0.,
+            ..Default::default()
+        };
+        let region = paint_image(
+            &mut y_map,
+            &mut PaintStroke::default(),
+            chunk_size,
+            center,
+            &brush,
+            0.5,
+            Paint::Value,
+        );
+        assert!(region.is_some());
+        let height = read_pixel(&y_map, center, chunk_size).unwrap()[0];
+        assert!((height - 0.7).abs() < 1e-4);
+        // pixels outside of the brush keep their height
+        let height = read_pixel(&y_map, Vec2::splat(0.5), chunk_size).unwrap()[0];
+        assert!((height - 0.2).abs() < 1e-4);
+    }
+}
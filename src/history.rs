@@ -0,0 +1,337 @@
+//! Contains the [`GrassEditHistory`], which records changes of the grass maps to undo and redo them.
+//!
+//! Tools changing the maps of the chunks at runtime, like the `GrassEditorPlugin`, call [`GrassEditHistory::track`]
+//! before they change an image and [`GrassEditHistory::finish_edit`] once the edit is done.
+//! Edits are undone and redone by sending a [`GrassHistoryCommand`]
+use bevy::{
+    math::URect,
+    prelude::*,
+    render::{primitives::Aabb, texture::TextureFormatPixelInfo},
+    utils::HashMap,
+};
+
+use crate::{
+    dithering::{dither_touched_regions, ComputeDither, DitheredBuffer},
+    map::DensityMap,
+};
+
+/// A [`Plugin`] that applies the [`GrassHistoryCommand`]s to the grass maps.
+///
+/// Undoing or redoing a change of a [`DensityMap`] only dithers the blades of the changed region again.
+/// Other maps, like the [`YMap`](crate::map::YMap), are uploaded to the gpu again
+pub struct GrassEditHistoryPlugin;
+impl Plugin for GrassEditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GrassEditHistory>()
+            .add_event::<GrassHistoryCommand>()
+            .add_systems(Update, apply_history_commands);
+    }
+}
+
+/// Undoes or redoes the edits of the [`GrassEditHistory`]
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrassHistoryCommand {
+    /// Reverts the last edit
+    Undo,
+    /// Applies the last reverted edit again
+    Redo,
+}
+
+/// The edits of the grass maps, which can be undone.
+///
+/// Each edit stores the changed region of the images it touched, so it stays small for local changes
+#[derive(Resource)]
+pub struct GrassEditHistory {
+    undo: Vec<GrassEdit>,
+    redo: Vec<GrassEdit>,
+    /// The images tracked by the unfinished edit
+    open: HashMap<AssetId<Image>, TrackedImage>,
+    /// The maximum amount of edits that can be undone
+    pub max_edits: usize,
+}
+impl Default for GrassEditHistory {
+    fn default() -> Self {
+        GrassEditHistory {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            open: HashMap::new(),
+            max_edits: 64,
+        }
+    }
+}
+/// An image tracked by the unfinished edit
+struct TrackedImage {
+    chunk: Entity,
+    image: Handle<Image>,
+    /// The image data before the edit
+    data: Vec<u8>,
+}
+/// The changes of one or more images that are undone together
+struct GrassEdit {
+    diffs: Vec<ImageDiff>,
+}
+/// The changed region of an image
+struct ImageDiff {
+    /// The chunk that changed the image
+    chunk: Entity,
+    image: Handle<Image>,
+    /// The changed region in pixels
+    region: URect,
+    /// The size of the image when it was changed
+    size: UVec2,
+    /// The rows of the region before the edit
+    before: Vec<u8>,
+    /// The rows of the region after the edit
+    after: Vec<u8>,
+}
+impl GrassEditHistory {
+    /// Starts tracking an image of a chunk, which is going to be changed.
+    ///
+    /// Has to be called before the image is changed.
+    /// All images tracked until [`finish_edit`](Self::finish_edit) is called are undone together
+    pub fn track(&mut self, chunk: Entity, handle: &Handle<Image>, image: &Image) {
+        self.open
+            .entry(handle.id())
+            .or_insert_with(|| TrackedImage {
+                chunk,
+                image: handle.clone(),
+                data: image.data.clone(),
+            });
+    }
+    /// Whether images are tracked by an unfinished edit
+    pub fn is_editing(&self) -> bool {
+        !self.open.is_empty()
+    }
+    /// Finishes the current edit, which can then be undone.
+    ///
+    /// Only the changed regions of the tracked images are stored.
+    /// Clears the edits that could be redone
+    pub fn finish_edit(&mut self, images: &Assets<Image>) {
+        let mut diffs = Vec::new();
+        for (_, tracked) in self.open.drain() {
+            let Some(image) = images.get(&tracked.image) else {
+                continue;
+            };
+            let size = image.size();
+            let pixel_size = image.texture_descriptor.format.pixel_size();
+            if tracked.data.len() != image.data.len() {
+                warn!(
+                    "The size of a map of chunk {:?} changed during an edit, the edit can't be undone",
+                    tracked.chunk
+                );
+                continue;
+            }
+            let Some(region) = changed_region(&tracked.data, &image.data, size, pixel_size) else {
+                continue;
+            };
+            diffs.push(ImageDiff {
+                chunk: tracked.chunk,
+                image: tracked.image,
+                region,
+                size,
+                before: copy_region(&tracked.data, size, pixel_size, region),
+                after: copy_region(&image.data, size, pixel_size, region),
+            });
+        }
+        if diffs.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push(GrassEdit { diffs });
+        if self.undo.len() > self.max_edits {
+            let overflow = self.undo.len() - self.max_edits;
+            self.undo.drain(..overflow);
+        }
+    }
+    /// The amount of edits that can be undone
+    pub fn undo_count(&self) -> usize {
+        self.undo.len()
+    }
+    /// The amount of edits that can be redone
+    pub fn redo_count(&self) -> usize {
+        self.redo.len()
+    }
+    /// Removes all edits
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open.clear();
+    }
+    /// Applies a [`GrassHistoryCommand`] to the images.
+    ///
+    /// Returns the changed regions relative to the size of each image
+    fn apply(
+        &mut self,
+        command: GrassHistoryCommand,
+        images: &mut Assets<Image>,
+    ) -> HashMap<AssetId<Image>, Rect> {
+        let (from, to) = match command {
+            GrassHistoryCommand::Undo => (&mut self.undo, &mut self.redo),
+            GrassHistoryCommand::Redo => (&mut self.redo, &mut self.undo),
+        };
+        let mut changed = HashMap::new();
+        let Some(edit) = from.pop() else {
+            return changed;
+        };
+        for diff in &edit.diffs {
+            let Some(image) = images.get_mut(&diff.image) else {
+                continue;
+            };
+            if image.size() != diff.size {
+                warn!(
+                    "The size of a map of chunk {:?} changed since the edit, the edit can't be applied",
+                    diff.chunk
+                );
+                continue;
+            }
+            let rows = match command {
+                GrassHistoryCommand::Undo => &diff.before,
+                GrassHistoryCommand::Redo => &diff.after,
+            };
+            let pixel_size = image.texture_descriptor.format.pixel_size();
+            paste_region(&mut image.data, diff.size, pixel_size, diff.region, rows);
+            let size = diff.size.as_vec2();
+            changed.insert(
+                diff.image.id(),
+                Rect::from_corners(
+                    diff.region.min.as_vec2() / size,
+                    diff.region.max.as_vec2() / size,
+                ),
+            );
+        }
+        to.push(edit);
+        changed
+    }
+}
+
+/// The smallest region containing all pixels that differ
+fn changed_region(before: &[u8], after: &[u8], size: UVec2, pixel_size: usize) -> Option<URect> {
+    let mut region: Option<URect> = None;
+    let row_length = size.x as usize * pixel_size;
+    for y in 0..size.y {
+        let start = y as usize * row_length;
+        let (before, after) = (
+            &before[start..start + row_length],
+            &after[start..start + row_length],
+        );
+        let Some(first) = before.iter().zip(after).position(|(a, b)| a != b) else {
+            continue;
+        };
+        let last = before
+            .iter()
+            .zip(after)
+            .rposition(|(a, b)| a != b)
+            .unwrap_or(first);
+        let row = URect::new(
+            (first / pixel_size) as u32,
+            y,
+            (last / pixel_size) as u32 + 1,
+            y + 1,
+        );
+        region = Some(region.map_or(row, |region| region.union(row)));
+    }
+    region
+}
+/// Copies the rows of a region of the image data
+fn copy_region(data: &[u8], size: UVec2, pixel_size: usize, region: URect) -> Vec<u8> {
+    let row_length = region.width() as usize * pixel_size;
+    let mut rows = Vec::with_capacity(row_length * region.height() as usize);
+    for y in region.min.y..region.max.y {
+        let start = (y as usize * size.x as usize + region.min.x as usize) * pixel_size;
+        rows.extend_from_slice(&data[start..start + row_length]);
+    }
+    rows
+}
+/// Writes the rows of a region into the image data
+fn paste_region(data: &mut [u8], size: UVec2, pixel_size: usize, region: URect, rows: &[u8]) {
+    let row_length = region.width() as usize * pixel_size;
+    for (row, y) in rows
+        .chunks_exact(row_length)
+        .zip(region.min.y..region.max.y)
+    {
+        let start = (y as usize * size.x as usize + region.min.x as usize) * pixel_size;
+        data[start..start + row_length].copy_from_slice(row);
+    }
+}
+
+fn apply_history_commands(
+    mut commands: EventReader<GrassHistoryCommand>,
+    mut history: ResMut<GrassEditHistory>,
+    mut images: ResMut<Assets<Image>>,
+    mut dithered: ResMut<Assets<DitheredBuffer>>,
+    chunks: Query<(&Aabb, &DensityMap, &Handle<DitheredBuffer>), Without<ComputeDither>>,
+) {
+    let mut changed: HashMap<AssetId<Image>, Rect> = HashMap::new();
+    for command in commands.read() {
+        // an unfinished edit is finished first, so it can be undone as well
+        if history.is_editing() {
+            history.finish_edit(&images);
+        }
+        for (image, region) in history.apply(*command, &mut images) {
+            changed
+                .entry(image)
+                .and_modify(|changed| *changed = changed.union(region))
+                .or_insert(region);
+        }
+    }
+    if changed.is_empty() {
+        return;
+    }
+    dither_touched_regions(&chunks, &changed, &images, &mut dithered);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        prelude::{Assets, Entity, Image},
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use super::{GrassEditHistory, GrassHistoryCommand};
+
+    #[test]
+    fn undo_and_redo_edits() {
+        let mut images = Assets::<Image>::default();
+        let handle = images.add(Image::new_fill(
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0],
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        ));
+        let mut history = GrassEditHistory::default();
+        let chunk = Entity::from_raw(1);
+
+        history.track(chunk, &handle, images.get(&handle).unwrap());
+        let image = images.get_mut(&handle).unwrap();
+        image.data[5] = 100;
+        image.data[10] = 200;
+        history.finish_edit(&images);
+        assert_eq!(history.undo_count(), 1);
+        // only the changed rows and columns are stored
+        assert_eq!(history.undo[0].diffs[0].after, vec![100, 0, 0, 200]);
+
+        let changed = history.apply(GrassHistoryCommand::Undo, &mut images);
+        assert_eq!(changed[&handle.id()].min.x, 0.25);
+        assert!(images.get(&handle).unwrap().data.iter().all(|v| *v == 0));
+        assert_eq!(history.redo_count(), 1);
+
+        history.apply(GrassHistoryCommand::Redo, &mut images);
+        let data = &images.get(&handle).unwrap().data;
+        assert_eq!((data[5], data[10]), (100, 200));
+
+        // a new edit can't be redone
+        history.apply(GrassHistoryCommand::Undo, &mut images);
+        history.track(chunk, &handle, images.get(&handle).unwrap());
+        images.get_mut(&handle).unwrap().data[0] = 1;
+        history.finish_edit(&images);
+        assert_eq!((history.undo_count(), history.redo_count()), (1, 0));
+    }
+}
@@ -16,17 +16,31 @@ use bevy::{
     },
 };
 
+pub mod bake;
+pub mod blade_mesh;
 pub mod bundle;
+pub mod chunk_asset;
+pub mod cutting;
+#[cfg(feature = "debug")]
+pub mod debug;
 pub mod dithering;
 
 pub mod diagnostic;
+pub mod displacement;
 #[cfg(feature = "editor")]
 pub mod editor;
+pub mod history;
 
 pub mod map;
+pub mod material;
+pub mod persistence;
+pub mod procedural;
 
 mod render;
+pub mod render_error;
+pub mod shader_hooks;
 pub mod warblers_plugin;
+pub mod wind;
 pub mod prelude {
     pub use crate::bundle::*;
     pub use crate::map::*;
@@ -44,18 +58,47 @@ pub struct GrassConfiguration {
     ///
     /// The direction of the wind is on the x,z plane.
     ///
-    /// Be aware that the strength of the wind is controlled by the length of the vector.
+    /// Be aware that the strength of the wind is scaled by the length of the vector.
     /// If you want to turn of wind in your game, you can just set this to `Vec2::ZERO`
     ///
     /// If you want to change the generel look of the wind and not only the wind direction/ speed
     /// you can also change the noise texture used for the wind that is stored in the
     /// [`GrassNoiseTexture`] resource
     pub wind: Vec2,
+    /// Scales how far the blades are bent by the wind
+    pub wind_strength: f32,
+    /// How fast the wind noise scrolls over the field in the direction of [`GrassConfiguration::wind`].
+    ///
+    /// Measured in pixels of the [`GrassNoiseTexture`] per second
+    pub wind_speed: f32,
+    /// The scale of the wind noise.
+    ///
+    /// Higher values result in smaller wind patterns
+    pub wind_scale: f32,
+    /// How many gusts pass over the field per second
+    pub gust_frequency: f32,
+    /// The distance in world units between two gusts passing over the field
+    pub gust_length: f32,
+    /// How much stronger the wind becomes during a gust.
+    ///
+    /// A value of 1 doubles the wind strength at the peak of a gust. 0 disables gusts
+    pub gust_amplitude: f32,
+    /// Shifts the wind animation of each blade by a random time in the range of `[-phase_variation / 2, phase_variation / 2]` seconds.
+    ///
+    /// Makes neighbouring blades move less uniformly
+    pub phase_variation: f32,
 }
 impl Default for GrassConfiguration {
     fn default() -> Self {
         GrassConfiguration {
             wind: Vec2::new(1.0, 1.0),
+            wind_strength: 1.0,
+            wind_speed: 70.0,
+            wind_scale: 35.0,
+            gust_frequency: 0.2,
+            gust_length: 60.0,
+            gust_amplitude: 0.0,
+            phase_variation: 0.0,
         }
     }
 }
@@ -1,4 +1,4 @@
-//! Contains the [`YMap`](crate::maps::YMap) and [`DensityMap`](crate::maps::DensityMap) component
+//! Contains the [`YMap`](crate::map::YMap), [`DensityMap`](crate::map::DensityMap) and [`ColorMap`](crate::map::ColorMap) component
 
 use bevy::{
     asset::Handle,
@@ -81,6 +81,43 @@ impl ExtractComponent for NormalMap {
     }
 }
 
+/// The color map tints the grass blades at a given position.
+///
+/// The area covered by the color map is defined by the area of the [`Aabb`](bevy::render::primitives::Aabb) component.
+/// The [`ColorMap`] texture will be scaled over the complete area,
+/// and both colors of the [`GrassColor`](crate::prelude::GrassColor) are multiplied with the color of the blade's pixel.
+/// White pixels keep the original color, so dry patches, flowers or paths can be painted on a white image.
+///
+/// Chunks without a [`ColorMap`] are not tinted
+#[derive(Reflect, Clone, Component)]
+pub struct ColorMap {
+    pub color_map: Handle<Image>,
+}
+impl ColorMap {
+    /// Creates a new `ColorMap`
+    pub fn new(color_map: Handle<Image>) -> Self {
+        ColorMap { color_map }
+    }
+}
+impl From<Handle<Image>> for ColorMap {
+    fn from(value: Handle<Image>) -> Self {
+        ColorMap { color_map: value }
+    }
+}
+impl ExtractComponent for ColorMap {
+    type QueryData = &'static Self;
+
+    type QueryFilter = ();
+
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        Some(ColorMap {
+            color_map: item.color_map.clone_weak(),
+        })
+    }
+}
+
 /// The density map defines the density of grass blades at a given positions.
 ///
 /// The area covered by the density map is defined by the area of the [`Aabb`](bevy::render::primitives::Aabb) component.
@@ -0,0 +1,342 @@
+//! Contains the [`GrassMaterial`] trait, which allows rendering grass with custom shaders.
+//!
+//! A material is an [`Asset`] implementing [`AsBindGroup`].
+//! Add the [`GrassMaterialPlugin`] of your material to the app and insert a `Handle<M>` on a grass chunk,
+//! to draw the chunk with the shaders of the material instead of the built-in grass shader.
+//!
+//! The bind group of the material is available in the shaders at `@group(7)`.
+//! The groups 0 to 6 are used by the grass pipeline and have the same layout as in the built-in grass shader.
+//! You can take a look at the grass_material example in the repository on how this might work
+use std::{hash::Hash, marker::PhantomData};
+
+use bevy::{
+    core_pipeline::core_3d::Opaque3d,
+    pbr::MeshPipelineKey,
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::{prepare_assets, RenderAssets},
+        render_phase::AddRenderCommand,
+        render_resource::{
+            AsBindGroup, AsBindGroupError, BindGroup, BindGroupLayout, RenderPipelineDescriptor,
+            ShaderRef, SpecializedMeshPipeline, SpecializedMeshPipelineError,
+            SpecializedMeshPipelines,
+        },
+        renderer::RenderDevice,
+        texture::FallbackImage,
+        Extract, Render, RenderApp, RenderSet,
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    render::{
+        grass_pipeline::{GrassPipeline, GrassRenderKey},
+        queue::queue_grass_material_buffers,
+        DrawGrassMaterial,
+    },
+    warblers_plugin::WarblersPlugin,
+};
+
+/// Materials are used to change how grass chunks are drawn.
+///
+/// Similar to bevy's [`Material`], a material can define its own vertex and fragment shader
+/// and upload arbitrary data to the gpu using [`AsBindGroup`].
+/// The bind group of the material is bound at `@group(7)`.
+///
+/// Custom vertex shaders have to provide the same vertex inputs as the built-in grass shader.
+/// Custom fragment shaders receive the output of the vertex shader,
+/// which is `VertexOutput` from `warbler_grass::types` for the built-in shader.
+/// The stages of the built-in shader can be imported from their modules,
+/// see [`shader_hooks`](crate::shader_hooks)
+pub trait GrassMaterial: Asset + AsBindGroup + Clone + Sized {
+    /// Returns the vertex shader of the material.
+    ///
+    /// [`ShaderRef::Default`] uses the vertex shader of the built-in grass shader
+    fn vertex_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+    /// Returns the fragment shader of the material.
+    ///
+    /// [`ShaderRef::Default`] uses the fragment shader of the built-in grass shader
+    fn fragment_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+    /// Customizes the render pipeline of the material after it was specialized by the grass pipeline
+    #[allow(unused_variables)]
+    #[inline]
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        key: GrassMaterialKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        Ok(())
+    }
+}
+/// Adds the render pipeline for drawing grass with the [`GrassMaterial`] `M` to an [`App`].
+///
+/// The pipeline of the material is built from the [`GrassPipeline`],
+/// so the plugin has to be added after the [`WarblersPlugin`].
+///
+/// # Panics
+/// Panics if the [`WarblersPlugin`] wasn't added before
+pub struct GrassMaterialPlugin<M: GrassMaterial>(PhantomData<M>);
+
+impl<M: GrassMaterial> Default for GrassMaterialPlugin<M> {
+    fn default() -> Self {
+        GrassMaterialPlugin(PhantomData)
+    }
+}
+impl<M: GrassMaterial> Plugin for GrassMaterialPlugin<M>
+where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    fn build(&self, app: &mut App) {
+        assert!(
+            app.is_plugin_added::<WarblersPlugin>(),
+            "The `WarblersPlugin` has to be added before the `GrassMaterialPlugin`"
+        );
+        app.init_asset::<M>();
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_command::<Opaque3d, DrawGrassMaterial<M>>()
+            .init_resource::<ExtractedGrassMaterials<M>>()
+            .init_resource::<RenderGrassMaterials<M>>()
+            .init_resource::<SpecializedMeshPipelines<GrassMaterialPipeline<M>>>()
+            .add_systems(
+                ExtractSchedule,
+                (
+                    extract_grass_materials::<M>,
+                    extract_grass_material_handles::<M>,
+                ),
+            )
+            .add_systems(
+                Render,
+                (
+                    prepare_grass_materials::<M>
+                        .in_set(RenderSet::PrepareAssets)
+                        .after(prepare_assets::<Image>),
+                    queue_grass_material_buffers::<M>.in_set(RenderSet::QueueMeshes),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<GrassMaterialPipeline<M>>();
+    }
+}
+/// The key used to specialize the render pipeline of a [`GrassMaterial`]
+pub struct GrassMaterialKey<M: GrassMaterial> {
+    pub(crate) grass_key: GrassRenderKey,
+    /// The data of the material, as returned by [`AsBindGroup`]
+    pub bind_group_data: M::Data,
+}
+impl<M: GrassMaterial> GrassMaterialKey<M> {
+    /// The key of the underlying mesh pipeline
+    pub fn mesh_key(&self) -> MeshPipelineKey {
+        self.grass_key.mesh_key
+    }
+}
+impl<M: GrassMaterial> Eq for GrassMaterialKey<M> where M::Data: PartialEq {}
+
+impl<M: GrassMaterial> PartialEq for GrassMaterialKey<M>
+where
+    M::Data: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.grass_key == other.grass_key && self.bind_group_data == other.bind_group_data
+    }
+}
+impl<M: GrassMaterial> Clone for GrassMaterialKey<M>
+where
+    M::Data: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            grass_key: self.grass_key.clone(),
+            bind_group_data: self.bind_group_data.clone(),
+        }
+    }
+}
+impl<M: GrassMaterial> Hash for GrassMaterialKey<M>
+where
+    M::Data: Hash,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.grass_key.hash(state);
+        self.bind_group_data.hash(state);
+    }
+}
+
+/// The [`GrassPipeline`] extended by the shaders and the bind group layout of a [`GrassMaterial`]
+#[derive(Resource)]
+pub(crate) struct GrassMaterialPipeline<M: GrassMaterial> {
+    grass_pipeline: GrassPipeline,
+    pub material_layout: BindGroupLayout,
+    vertex_shader: Option<Handle<Shader>>,
+    fragment_shader: Option<Handle<Shader>>,
+    marker: PhantomData<M>,
+}
+impl<M: GrassMaterial> FromWorld for GrassMaterialPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let load_shader = |shader_ref: ShaderRef| match shader_ref {
+            ShaderRef::Default => None,
+            ShaderRef::Handle(handle) => Some(handle),
+            ShaderRef::Path(path) => Some(asset_server.load(path)),
+        };
+        let vertex_shader = load_shader(M::vertex_shader());
+        let fragment_shader = load_shader(M::fragment_shader());
+        let render_device = world.resource::<RenderDevice>();
+        GrassMaterialPipeline {
+            grass_pipeline: world.resource::<GrassPipeline>().clone(),
+            material_layout: M::bind_group_layout(render_device),
+            vertex_shader,
+            fragment_shader,
+            marker: PhantomData,
+        }
+    }
+}
+impl<M: GrassMaterial> SpecializedMeshPipeline for GrassMaterialPipeline<M>
+where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    type Key = GrassMaterialKey<M>;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self
+            .grass_pipeline
+            .specialize(key.grass_key.clone(), layout)?;
+        descriptor.label = Some("Grass Material Render Pipeline".into());
+        if let Some(vertex_shader) = &self.vertex_shader {
+            descriptor.vertex.shader = vertex_shader.clone();
+        }
+        if let Some(fragment_shader) = &self.fragment_shader {
+            descriptor.fragment.as_mut().unwrap().shader = fragment_shader.clone();
+        }
+        descriptor.layout.push(self.material_layout.clone());
+        M::specialize(&mut descriptor, layout, key)?;
+        Ok(descriptor)
+    }
+}
+
+/// Marks chunks in the render world that are drawn with a [`GrassMaterial`]
+#[derive(Component)]
+pub(crate) struct GrassMaterialFlag;
+
+/// The bind group of a [`GrassMaterial`]
+pub(crate) struct PreparedGrassMaterial<M: GrassMaterial> {
+    pub bind_group: BindGroup,
+    pub key: M::Data,
+}
+/// The [`GrassMaterial`]s which changed since the last frame
+#[derive(Resource)]
+pub(crate) struct ExtractedGrassMaterials<M: GrassMaterial> {
+    extracted: Vec<(AssetId<M>, M)>,
+    removed: Vec<AssetId<M>>,
+}
+impl<M: GrassMaterial> Default for ExtractedGrassMaterials<M> {
+    fn default() -> Self {
+        Self {
+            extracted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+/// Stores the prepared [`GrassMaterial`]s for as long as they exist
+#[derive(Resource, Deref, DerefMut)]
+pub(crate) struct RenderGrassMaterials<M: GrassMaterial>(
+    pub HashMap<AssetId<M>, PreparedGrassMaterial<M>>,
+);
+impl<M: GrassMaterial> Default for RenderGrassMaterials<M> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+/// Extracts the handles of the materials and marks the chunks using them
+fn extract_grass_material_handles<M: GrassMaterial>(
+    mut commands: Commands,
+    chunks: Extract<Query<(Entity, &Handle<M>)>>,
+) {
+    let mut values = Vec::new();
+    for (entity, material) in chunks.iter() {
+        values.push((entity, (material.clone_weak(), GrassMaterialFlag)));
+    }
+    commands.insert_or_spawn_batch(values);
+}
+/// Extracts all created or modified [`GrassMaterial`]s into the render world
+fn extract_grass_materials<M: GrassMaterial>(
+    mut commands: Commands,
+    mut events: Extract<EventReader<AssetEvent<M>>>,
+    assets: Extract<Res<Assets<M>>>,
+) {
+    let mut changed_assets = HashSet::default();
+    let mut removed = Vec::new();
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                changed_assets.insert(*id);
+            }
+            AssetEvent::Removed { id } => {
+                changed_assets.remove(id);
+                removed.push(*id);
+            }
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+    let extracted = changed_assets
+        .into_iter()
+        .filter_map(|id| assets.get(id).map(|asset| (id, asset.clone())))
+        .collect();
+    commands.insert_resource(ExtractedGrassMaterials { extracted, removed });
+}
+/// Creates the bind groups of the extracted [`GrassMaterial`]s.
+///
+/// Materials whose images aren't loaded yet are prepared again in the next frame
+fn prepare_grass_materials<M: GrassMaterial>(
+    mut retry_next_frame: Local<Vec<(AssetId<M>, M)>>,
+    mut extracted_assets: ResMut<ExtractedGrassMaterials<M>>,
+    mut render_materials: ResMut<RenderGrassMaterials<M>>,
+    render_device: Res<RenderDevice>,
+    images: Res<RenderAssets<Image>>,
+    fallback_image: Res<FallbackImage>,
+    pipeline: Res<GrassMaterialPipeline<M>>,
+) {
+    for removed in std::mem::take(&mut extracted_assets.removed) {
+        render_materials.remove(&removed);
+    }
+    let queued = std::mem::take(&mut *retry_next_frame);
+    for (id, material) in queued
+        .into_iter()
+        .chain(std::mem::take(&mut extracted_assets.extracted))
+    {
+        match material.as_bind_group(
+            &pipeline.material_layout,
+            &render_device,
+            &images,
+            &fallback_image,
+        ) {
+            Ok(prepared) => {
+                render_materials.insert(
+                    id,
+                    PreparedGrassMaterial {
+                        bind_group: prepared.bind_group,
+                        key: prepared.data,
+                    },
+                );
+            }
+            Err(AsBindGroupError::RetryNextUpdate) => {
+                retry_next_frame.push((id, material));
+            }
+        }
+    }
+}
@@ -0,0 +1,598 @@
+//! Contains functions to save the maps of a grass chunk to disk and to load them again.
+//!
+//! A saved chunk is a directory holding the maps as png images and a [`GrassChunkManifest`]
+//! with the parameters of the chunk, stored as `chunk.grass.ron`.
+//! The manifest can also be loaded as a [`GrassChunkAsset`](crate::chunk_asset::GrassChunkAsset).
+//! Maps painted or generated at runtime can be saved this way and loaded when the game starts again.
+//! ```no_run
+//! # use bevy::prelude::*;
+//! use warbler_grass::persistence::{load_chunk, save_chunk, MapEncoding};
+//!
+//! fn save(world: &mut World, chunk: Entity) {
+//!     save_chunk(world, chunk, "saves/meadow", MapEncoding::Png16).unwrap();
+//! }
+//! fn load(world: &mut World) {
+//!     let chunk = load_chunk("saves/meadow", world).unwrap();
+//!     chunk.spawn(world);
+//! }
+//! ```
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    asset::{ParseAssetPathError, ReadAssetBytesError},
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use image::{DynamicImage, ImageBuffer, Luma};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dithering::DitherComputeError,
+    map::{ColorMap, DensityMap, NormalMap, YMap},
+    prelude::{GrassColor, WarblerHeight, WarblersBundle},
+    warblers_plugin::{DEFAULT_NORMAL_MAP_HANDLE, GRASS_MESH_HANDLE},
+};
+
+/// The file name of the [`GrassChunkManifest`] in the directory of a saved chunk
+pub const MANIFEST_FILE: &str = "chunk.grass.ron";
+const DENSITY_MAP_FILE: &str = "density_map.png";
+const Y_MAP_FILE: &str = "y_map.png";
+const NORMAL_MAP_FILE: &str = "normal_map.png";
+const HEIGHT_MAP_FILE: &str = "height_map.png";
+const COLOR_MAP_FILE: &str = "color_map.png";
+
+/// The parameters of a saved grass chunk.
+///
+/// The maps are referenced by their file names, relative to the manifest
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GrassChunkManifest {
+    /// The minimum corner of the [`Aabb`] of the chunk
+    pub aabb_min: [f32; 3],
+    /// The maximum corner of the [`Aabb`] of the chunk
+    pub aabb_max: [f32; 3],
+    /// The density of the [`DensityMap`]
+    pub density: f32,
+    pub density_map: String,
+    pub y_map: String,
+    /// The [`NormalMap`], if the chunk doesn't use the default normal map
+    #[serde(default)]
+    pub normal_map: Option<String>,
+    pub height: SavedHeight,
+    /// The main color of the [`GrassColor`] in srgb
+    pub main_color: [f32; 4],
+    /// The bottom color of the [`GrassColor`] in srgb
+    pub bottom_color: [f32; 4],
+    #[serde(default)]
+    pub color_map: Option<String>,
+    /// The asset path of the blade mesh, if the chunk doesn't use the default mesh
+    #[serde(default)]
+    pub mesh: Option<String>,
+}
+/// The saved [`WarblerHeight`] of a chunk
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SavedHeight {
+    Uniform(f32),
+    /// The file name of the height map
+    Texture(String),
+}
+
+/// The bit depth the maps are saved with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapEncoding {
+    /// All maps are saved as 8 bit png images
+    #[default]
+    Png8,
+    /// The [`YMap`] and the height map are saved as 16 bit grayscale png images,
+    /// which gives smoother terrain.
+    ///
+    /// 16 bit maps are loaded as [`TextureFormat::R32Float`] images
+    Png16,
+}
+
+#[derive(Debug)]
+pub enum GrassFileError {
+    /// The entity doesn't exist or isn't a grass chunk
+    NotAChunk(Entity),
+    /// The image of the named map isn't loaded
+    MissingImage(&'static str),
+    /// The image can't be converted to a png image
+    UnsupportedFormat(TextureFormat),
+    Io(std::io::Error),
+    Image(image::ImageError),
+    SerializeManifest(ron::Error),
+    DeserializeManifest(ron::de::SpannedError),
+    /// A map referenced by a [`GrassChunkAsset`](crate::chunk_asset::GrassChunkAsset) can't be read
+    ReadAsset(ReadAssetBytesError),
+    /// A path in a [`GrassChunkAsset`](crate::chunk_asset::GrassChunkAsset) is invalid
+    AssetPath(ParseAssetPathError),
+    /// The density map of a chunk can't be baked
+    Dither(DitherComputeError),
+    /// The file isn't a valid [`GrassBake`](crate::bake::GrassBake)
+    InvalidBake(&'static str),
+}
+impl Display for GrassFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrassFileError::NotAChunk(e) => write!(f, "Entity {e:?} is not a grass chunk. Chunks need at least the components of the `WarblersBundle`"),
+            GrassFileError::MissingImage(map) => write!(f, "The image of the {map} is not loaded"),
+            GrassFileError::UnsupportedFormat(format) => write!(f, "Images with the format {format:?} can't be saved"),
+            GrassFileError::Io(error) => error.fmt(f),
+            GrassFileError::Image(error) => error.fmt(f),
+            GrassFileError::SerializeManifest(error) => error.fmt(f),
+            GrassFileError::DeserializeManifest(error) => error.fmt(f),
+            GrassFileError::ReadAsset(error) => error.fmt(f),
+            GrassFileError::AssetPath(error) => error.fmt(f),
+            GrassFileError::Dither(error) => error.fmt(f),
+            GrassFileError::InvalidBake(reason) => write!(f, "Invalid grass bake: {reason}"),
+        }
+    }
+}
+impl Error for GrassFileError {}
+impl From<std::io::Error> for GrassFileError {
+    fn from(value: std::io::Error) -> Self {
+        GrassFileError::Io(value)
+    }
+}
+impl From<image::ImageError> for GrassFileError {
+    fn from(value: image::ImageError) -> Self {
+        GrassFileError::Image(value)
+    }
+}
+impl From<ron::Error> for GrassFileError {
+    fn from(value: ron::Error) -> Self {
+        GrassFileError::SerializeManifest(value)
+    }
+}
+impl From<ReadAssetBytesError> for GrassFileError {
+    fn from(value: ReadAssetBytesError) -> Self {
+        GrassFileError::ReadAsset(value)
+    }
+}
+impl From<ParseAssetPathError> for GrassFileError {
+    fn from(value: ParseAssetPathError) -> Self {
+        GrassFileError::AssetPath(value)
+    }
+}
+impl From<DitherComputeError> for GrassFileError {
+    fn from(value: DitherComputeError) -> Self {
+        GrassFileError::Dither(value)
+    }
+}
+impl From<ron::de::SpannedError> for GrassFileError {
+    fn from(value: ron::de::SpannedError) -> Self {
+        GrassFileError::DeserializeManifest(value)
+    }
+}
+
+/// How the pixels of a map are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MapPixels {
+    Gray8,
+    Gray16,
+    Rgba8,
+}
+
+/// Saves the maps and parameters of a chunk into a directory, which is created if needed.
+///
+/// The images of the maps have to be loaded.
+/// A blade mesh that wasn't loaded from an asset path can't be referenced,
+/// so the chunk is saved with the default mesh instead
+pub fn save_chunk(
+    world: &World,
+    chunk: Entity,
+    directory: impl AsRef<Path>,
+    encoding: MapEncoding,
+) -> Result<GrassChunkManifest, GrassFileError> {
+    let directory = directory.as_ref();
+    let entity = world
+        .get_entity(chunk)
+        .ok_or(GrassFileError::NotAChunk(chunk))?;
+    let (Some(aabb), Some(density_map), Some(y_map), Some(height), Some(color)) = (
+        entity.get::<Aabb>(),
+        entity.get::<DensityMap>(),
+        entity.get::<YMap>(),
+        entity.get::<WarblerHeight>(),
+        entity.get::<GrassColor>(),
+    ) else {
+        return Err(GrassFileError::NotAChunk(chunk));
+    };
+    let images = world.resource::<Assets<Image>>();
+    let scalar = match encoding {
+        MapEncoding::Png8 => MapPixels::Gray8,
+        MapEncoding::Png16 => MapPixels::Gray16,
+    };
+    fs::create_dir_all(directory)?;
+    let save = |handle: &Handle<Image>, name: &'static str, file: &str, pixels: MapPixels| {
+        let image = images
+            .get(handle)
+            .ok_or(GrassFileError::MissingImage(name))?;
+        save_map(image, &directory.join(file), pixels)?;
+        Ok::<String, GrassFileError>(file.to_string())
+    };
+
+    let density_map_file = save(
+        &density_map.density_map,
+        "density map",
+        DENSITY_MAP_FILE,
+        MapPixels::Gray8,
+    )?;
+    let y_map_file = save(&y_map.y_map, "y-map", Y_MAP_FILE, scalar)?;
+    let normal_map = match entity.get::<NormalMap>() {
+        Some(normal_map) if normal_map.normal_map.id() != DEFAULT_NORMAL_MAP_HANDLE.id() => {
+            Some(save(
+                &normal_map.normal_map,
+                "normal map",
+                NORMAL_MAP_FILE,
+                MapPixels::Rgba8,
+            )?)
+        }
+        _ => None,
+    };
+    let height = match height {
+        WarblerHeight::Uniform(height) => SavedHeight::Uniform(*height),
+        WarblerHeight::Texture(texture) => {
+            SavedHeight::Texture(save(texture, "height map", HEIGHT_MAP_FILE, scalar)?)
+        }
+    };
+    let color_map = match entity.get::<ColorMap>() {
+        Some(color_map) => Some(save(
+            &color_map.color_map,
+            "color map",
+            COLOR_MAP_FILE,
+            MapPixels::Rgba8,
+        )?),
+        None => None,
+    };
+    let mesh = match entity.get::<Handle<Mesh>>() {
+        Some(mesh) if mesh.id() != GRASS_MESH_HANDLE.id() => {
+            let path = world
+                .get_resource::<AssetServer>()
+                .and_then(|asset_server| asset_server.get_path(mesh.id()));
+            if path.is_none() {
+                warn!("The mesh of chunk {chunk:?} has no asset path, the chunk is saved with the default mesh");
+            }
+            path.map(|path| path.to_string())
+        }
+        _ => None,
+    };
+
+    let manifest = GrassChunkManifest {
+        aabb_min: aabb.min().to_array(),
+        aabb_max: aabb.max().to_array(),
+        density: density_map.density,
+        density_map: density_map_file,
+        y_map: y_map_file,
+        normal_map,
+        height,
+        main_color: color.main_color.as_rgba_f32(),
+        bottom_color: color.bottom_color.as_rgba_f32(),
+        color_map,
+        mesh,
+    };
+    let manifest_string = ron::ser::to_string_pretty(&manifest, Default::default())?;
+    fs::write(directory.join(MANIFEST_FILE), manifest_string)?;
+    Ok(manifest)
+}
+
+/// A chunk loaded by [`load_chunk`]
+pub struct LoadedGrassChunk {
+    pub manifest: GrassChunkManifest,
+    pub bundle: WarblersBundle,
+    pub color_map: Option<ColorMap>,
+}
+impl LoadedGrassChunk {
+    /// Spawns the chunk
+    pub fn spawn(self, world: &mut World) -> Entity {
+        let mut entity = world.spawn(self.bundle);
+        if let Some(color_map) = self.color_map {
+            entity.insert(color_map);
+        }
+        entity.id()
+    }
+}
+
+/// Reads the [`GrassChunkManifest`] of a chunk saved in the directory
+pub fn read_manifest(directory: impl AsRef<Path>) -> Result<GrassChunkManifest, GrassFileError> {
+    let manifest = fs::read_to_string(directory.as_ref().join(MANIFEST_FILE))?;
+    Ok(ron::from_str(&manifest)?)
+}
+
+/// Loads a chunk saved by [`save_chunk`].
+///
+/// The maps are added to the [`Assets<Image>`] and the mesh is loaded by the [`AssetServer`]
+pub fn load_chunk(
+    directory: impl AsRef<Path>,
+    world: &mut World,
+) -> Result<LoadedGrassChunk, GrassFileError> {
+    let directory = directory.as_ref();
+    let manifest = read_manifest(directory)?;
+    let path = |file: &String| -> PathBuf { directory.join(file) };
+
+    let density_map = load_map(&path(&manifest.density_map), false)?;
+    let y_map = load_map(&path(&manifest.y_map), false)?;
+    let normal_map = match &manifest.normal_map {
+        Some(file) => Some(load_map(&path(file), false)?),
+        None => None,
+    };
+    let height_map = match &manifest.height {
+        SavedHeight::Texture(file) => Some(load_map(&path(file), false)?),
+        SavedHeight::Uniform(_) => None,
+    };
+    let color_map = match &manifest.color_map {
+        Some(file) => Some(load_map(&path(file), true)?),
+        None => None,
+    };
+
+    let grass_mesh = match &manifest.mesh {
+        Some(mesh) => match world.get_resource::<AssetServer>() {
+            Some(asset_server) => asset_server.load(mesh.clone()),
+            None => {
+                warn!("The mesh {mesh} can't be loaded without an `AssetServer`, the default mesh is used");
+                GRASS_MESH_HANDLE
+            }
+        },
+        None => GRASS_MESH_HANDLE,
+    };
+    let mut images = world.resource_mut::<Assets<Image>>();
+    let height = match (&manifest.height, height_map) {
+        (SavedHeight::Uniform(height), _) => WarblerHeight::Uniform(*height),
+        (SavedHeight::Texture(_), Some(texture)) => WarblerHeight::Texture(images.add(texture)),
+        (SavedHeight::Texture(_), None) => unreachable!("the height map is loaded above"),
+    };
+    let bundle = WarblersBundle {
+        grass_mesh,
+        y_map: YMap::new(images.add(y_map)),
+        normal_map: normal_map.map_or(DEFAULT_NORMAL_MAP_HANDLE.into(), |normal_map| {
+            images.add(normal_map).into()
+        }),
+        density_map: DensityMap::new(images.add(density_map), manifest.density),
+        height,
+        grass_color: GrassColor {
+            main_color: Color::rgba_from_array(manifest.main_color),
+            bottom_color: Color::rgba_from_array(manifest.bottom_color),
+        },
+        aabb: Aabb::from_min_max(manifest.aabb_min.into(), manifest.aabb_max.into()),
+        ..default()
+    };
+    let color_map = color_map.map(|color_map| ColorMap::new(images.add(color_map)));
+    Ok(LoadedGrassChunk {
+        manifest,
+        bundle,
+        color_map,
+    })
+}
+
+/// Converts an image into a dynamic image, 32 bit float maps are converted to 16 bit
+fn to_dynamic(image: &Image) -> Result<DynamicImage, GrassFileError> {
+    let format = image.texture_descriptor.format;
+    if format == TextureFormat::R32Float {
+        let values: &[f32] = bytemuck::cast_slice(&image.data);
+        let pixels = values
+            .iter()
+            .map(|value| (value.clamp(0., 1.) * u16::MAX as f32).round() as u16)
+            .collect();
+        return ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(image.width(), image.height(), pixels)
+            .map(DynamicImage::ImageLuma16)
+            .ok_or(GrassFileError::UnsupportedFormat(format));
+    }
+    image
+        .clone()
+        .try_into_dynamic()
+        .map_err(|_| GrassFileError::UnsupportedFormat(format))
+}
+fn save_map(image: &Image, path: &Path, pixels: MapPixels) -> Result<(), GrassFileError> {
+    let dynamic = to_dynamic(image)?;
+    match pixels {
+        MapPixels::Gray8 => dynamic.into_luma8().save(path)?,
+        MapPixels::Gray16 => dynamic.into_luma16().save(path)?,
+        MapPixels::Rgba8 => dynamic.into_rgba8().save(path)?,
+    }
+    Ok(())
+}
+/// Loads a saved map. Grayscale maps keep a single channel,
+/// 16 bit maps are loaded as [`TextureFormat::R32Float`] images,
+/// since 16 bit normalized formats aren't supported on all platforms
+fn load_map(path: &Path, is_srgb: bool) -> Result<Image, GrassFileError> {
+    Ok(decode_map(&fs::read(path)?, is_srgb)?)
+}
+/// Decodes the bytes of a saved map
+pub(crate) fn decode_map(bytes: &[u8], is_srgb: bool) -> Result<Image, image::ImageError> {
+    let dynamic = image::load_from_memory(bytes)?;
+    let size = Extent3d {
+        width: dynamic.width(),
+        height: dynamic.height(),
+        depth_or_array_layers: 1,
+    };
+    let (data, format) = match dynamic {
+        DynamicImage::ImageLuma8(buffer) => (buffer.into_raw(), TextureFormat::R8Unorm),
+        DynamicImage::ImageLuma16(buffer) => {
+            let values: Vec<f32> = buffer
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+                .collect();
+            (
+                bytemuck::cast_slice(&values).to_vec(),
+                TextureFormat::R32Float,
+            )
+        }
+        dynamic => {
+            return Ok(Image::from_dynamic(
+                dynamic,
+                is_srgb,
+                RenderAssetUsages::default(),
+            ))
+        }
+    };
+    Ok(Image::new(
+        size,
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        prelude::*,
+        render::{
+            primitives::Aabb,
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::{load_chunk, save_chunk, MapEncoding, SavedHeight};
+    use crate::prelude::{ColorMap, DensityMap, GrassColor, WarblerHeight, WarblersBundle, YMap};
+
+    /// A directory only used by a single test, which is removed even if the test fails
+    struct TestDirectory(PathBuf);
+    impl TestDirectory {
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            TestDirectory(std::env::temp_dir().join(format!(
+                "warbler_grass_persistence_test_{}_{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            )))
+        }
+    }
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn gradient(format: TextureFormat) -> Image {
+        Image::new(
+            Extent3d {
+                width: 16,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..16)
+                .flat_map(|v| vec![v * 16; format.block_copy_size(None).unwrap() as usize])
+                .collect(),
+            format,
+            RenderAssetUsages::default(),
+        )
+    }
+    #[test]
+    fn chunks_are_saved_and_loaded() {
+        let directory = TestDirectory::new();
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let density_map = images.add(gradient(TextureFormat::R8Unorm));
+        let y_map = images.add(gradient(TextureFormat::R8Unorm));
+        let height_map = images.add(gradient(TextureFormat::R8Unorm));
+        let color_map = images.add(gradient(TextureFormat::Rgba8UnormSrgb));
+        let color = GrassColor {
+            main_color: Color::rgb(0.1, 0.2, 0.3),
+            bottom_color: Color::WHITE,
+        };
+        let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::new(10., 2., 5.));
+        let chunk = world
+            .spawn((
+                WarblersBundle {
+                    density_map: DensityMap::new(density_map, 3.),
+                    y_map: YMap::new(y_map),
+                    height: WarblerHeight::Texture(height_map),
+                    grass_color: color.clone(),
+                    aabb,
+                    ..default()
+                },
+                ColorMap::new(color_map),
+            ))
+            .id();
+
+        let manifest = save_chunk(&world, chunk, &directory.0, MapEncoding::Png16).unwrap();
+        assert_eq!(
+            manifest.height,
+            SavedHeight::Texture("height_map.png".into())
+        );
+        assert_eq!(manifest.normal_map, None);
+        let loaded = load_chunk(&directory.0, &mut world).unwrap();
+        assert_eq!(loaded.manifest, manifest);
+        assert_eq!(loaded.bundle.aabb.half_extents, aabb.half_extents);
+        assert_eq!(loaded.bundle.density_map.density, 3.);
+        assert_eq!(loaded.bundle.grass_color.main_color, color.main_color);
+        assert!(loaded.color_map.is_some());
+
+        let images = world.resource::<Assets<Image>>();
+        // the 16 bit y-map keeps the values of the original map
+        let y_map = images.get(&loaded.bundle.y_map.y_map).unwrap();
+        assert_eq!(y_map.texture_descriptor.format, TextureFormat::R32Float);
+        let values: &[f32] = bytemuck::cast_slice(&y_map.data);
+        assert_eq!(values[1], 16. / 255.);
+        let density_map = images.get(&loaded.bundle.density_map.density_map).unwrap();
+        assert_eq!(
+            density_map.texture_descriptor.format,
+            TextureFormat::R8Unorm
+        );
+        assert_eq!(density_map.data[15], 240);
+    }
+    #[cfg(feature = "editor")]
+    #[test]
+    fn loaded_16_bit_maps_can_be_painted() {
+        use crate::editor::{paint_image, read_pixel, GrassBrush, Paint, PaintStroke};
+
+        let directory = TestDirectory::new();
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let density_map = images.add(gradient(TextureFormat::R8Unorm));
+        let y_map = images.add(gradient(TextureFormat::R8Unorm));
+        let chunk = world
+            .spawn(WarblersBundle {
+                density_map: DensityMap::new(density_map, 1.),
+                y_map: YMap::new(y_map),
+                aabb: Aabb::from_min_max(Vec3::ZERO, Vec3::new(16., 2., 1.)),
+                ..default()
+            })
+            .id();
+        save_chunk(&world, chunk, &directory.0, MapEncoding::Png16).unwrap();
+        let loaded = load_chunk(&directory.0, &mut world).unwrap();
+
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let y_map = images.get_mut(&loaded.bundle.y_map.y_map).unwrap();
+        let chunk_size = Vec2::new(16., 1.);
+        // the pixels of the gradient are one unit wide
+        let center = Vec2::new(1.5, 0.5);
+        let height = read_pixel(y_map, center, chunk_size).unwrap()[0];
+        assert_eq!(height, 16. / 255.);
+        let brush = GrassBrush {
+            radius: 0.5,
+            falloff: 0.,
+            ..default()
+        };
+        paint_image(
+            y_map,
+            &mut PaintStroke::default(),
+            chunk_size,
+            center,
+            &brush,
+            0.5,
+            Paint::Value,
+        )
+        .unwrap();
+        let height = read_pixel(y_map, center, chunk_size).unwrap()[0];
+        assert!((height - (16. / 255. + 0.5)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,401 @@
+//! Contains the [`ProceduralGrassPlugin`], which spawns grass in a ring of tiles around an anchor entity.
+//!
+//! Instead of authoring [`DensityMap`] and [`YMap`] textures by hand, the maps of each tile are generated
+//! from a [`GrassGenerator`] evaluated at world positions.
+//! Since the generator only depends on the world position, a tile always looks the same no matter when or how often it is generated.
+use std::sync::Arc;
+
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    bundle::{GrassColor, WarblerHeight, WarblersBundle},
+    dithering::{add_dither_task, DitheredBuffer},
+    map::{DensityMap, YMap},
+    warblers_plugin::GRASS_MESH_HANDLE,
+};
+
+/// Generates the grass of a [`ProceduralGrass`] field.
+///
+/// Both functions receive a position on the x,z plane in world space.
+/// The values should only depend on that position, otherwise recycled tiles won't match their neighbours
+pub trait GrassGenerator: Send + Sync + 'static {
+    /// The density of the grass at the given position.
+    ///
+    /// Should be in the range of `[0, 1]`, where 1 corresponds to a white pixel in a [`DensityMap`]
+    fn density(&self, position: Vec2) -> f32;
+    /// The y position of the ground at the given position.
+    ///
+    /// The value is clamped to `[0, ProceduralGrass::max_y]`
+    fn y(&self, _position: Vec2) -> f32 {
+        0.
+    }
+}
+/// Any function from a position to a density can be used as a flat [`GrassGenerator`]
+impl<F> GrassGenerator for F
+where
+    F: Fn(Vec2) -> f32 + Send + Sync + 'static,
+{
+    fn density(&self, position: Vec2) -> f32 {
+        self(position)
+    }
+}
+
+/// A [`GrassGenerator`] based on value noise.
+///
+/// Useful if you don't have a terrain function of your own
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseGenerator {
+    /// The seed of the noise. Different seeds generate different fields
+    pub seed: u32,
+    /// The frequency of the density noise. Smaller values create bigger patches of grass
+    pub density_frequency: f32,
+    /// The amount of noise layers added on top of each other
+    pub octaves: u32,
+    /// The frequency of the y noise
+    pub y_frequency: f32,
+    /// The maximum y position of the ground in world units
+    pub y_amplitude: f32,
+}
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        NoiseGenerator {
+            seed: 0,
+            density_frequency: 0.05,
+            octaves: 3,
+            y_frequency: 0.01,
+            y_amplitude: 0.,
+        }
+    }
+}
+impl NoiseGenerator {
+    fn fbm(&self, position: Vec2, seed: u32) -> f32 {
+        let mut value = 0.;
+        let mut amplitude = 0.5;
+        let mut total = 0.;
+        let mut position = position;
+        for octave in 0..self.octaves.max(1) {
+            value += value_noise(position, seed.wrapping_add(octave)) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            position *= 2.;
+        }
+        value / total
+    }
+}
+impl GrassGenerator for NoiseGenerator {
+    fn density(&self, position: Vec2) -> f32 {
+        self.fbm(position * self.density_frequency, self.seed)
+    }
+    fn y(&self, position: Vec2) -> f32 {
+        self.fbm(position * self.y_frequency, self.seed.wrapping_add(7919)) * self.y_amplitude
+    }
+}
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = seed
+        .wrapping_mul(0x27d4_eb2d)
+        .wrapping_add((x as u32).wrapping_mul(0x8da6_b343))
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+/// Smoothly interpolated value noise in the range `[0, 1]`
+fn value_noise(position: Vec2, seed: u32) -> f32 {
+    let cell = position.floor();
+    let fraction = position - cell;
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    // smoothstep
+    let t = fraction * fraction * (Vec2::splat(3.) - 2. * fraction);
+    let a = hash(x, y, seed);
+    let b = hash(x + 1, y, seed);
+    let c = hash(x, y + 1, seed);
+    let d = hash(x + 1, y + 1, seed);
+    let top = a + (b - a) * t.x;
+    let bottom = c + (d - c) * t.x;
+    top + (bottom - top) * t.y
+}
+
+/// A [resource](bevy::prelude::Resource) defining the procedural grass spawned around the [`ProceduralGrassAnchor`].
+///
+/// The field is split into square tiles of `tile_size`.
+/// All tiles in a square ring of `radius` tiles around the anchor are kept alive.
+/// Tiles leaving the ring are reused for tiles entering it.
+#[derive(Resource, Clone)]
+pub struct ProceduralGrass {
+    /// Generates the density and y position of the grass
+    pub generator: Arc<dyn GrassGenerator>,
+    /// The width and depth of a single tile in world units
+    pub tile_size: f32,
+    /// The amount of tiles spawned in each direction of the anchor tile.
+    ///
+    /// A radius of 2 spawns a field of 5x5 tiles
+    pub radius: u32,
+    /// The resolution of the generated maps of a tile in pixels
+    pub map_resolution: u32,
+    /// The density of the grass. See [`DensityMap::density`]
+    pub density: f32,
+    /// The maximal y position of the grass. Corresponds to the height of the [`Aabb`] of each tile
+    pub max_y: f32,
+    /// The height of the grass blades
+    pub height: f32,
+    /// The color of the grass blades
+    pub grass_color: GrassColor,
+    /// The mesh of the grass blades
+    pub grass_mesh: Handle<Mesh>,
+}
+impl Default for ProceduralGrass {
+    fn default() -> Self {
+        ProceduralGrass {
+            generator: Arc::new(NoiseGenerator::default()),
+            tile_size: 32.,
+            radius: 2,
+            map_resolution: 32,
+            density: 2.,
+            max_y: 1.,
+            height: 1.,
+            grass_color: GrassColor::default(),
+            grass_mesh: GRASS_MESH_HANDLE,
+        }
+    }
+}
+impl ProceduralGrass {
+    /// Creates a new field using the given generator and default values for everything else
+    pub fn new(generator: impl GrassGenerator) -> Self {
+        ProceduralGrass {
+            generator: Arc::new(generator),
+            ..default()
+        }
+    }
+    /// The coordinate of the tile containing the given world position
+    pub fn tile_coordinate(&self, position: Vec3) -> IVec2 {
+        (position.xz() / self.tile_size).floor().as_ivec2()
+    }
+    fn density_image(&self, coordinate: IVec2) -> Image {
+        self.generate_image(coordinate, |position| {
+            self.generator.density(position).clamp(0., 1.)
+        })
+    }
+    fn y_image(&self, coordinate: IVec2) -> Image {
+        let max_y = self.max_y.max(f32::EPSILON);
+        self.generate_image(coordinate, |position| {
+            (self.generator.y(position) / max_y).clamp(0., 1.)
+        })
+    }
+    /// Samples `f` at the center of each pixel of the tile and stores the results in a r8 image
+    fn generate_image(&self, coordinate: IVec2, f: impl Fn(Vec2) -> f32) -> Image {
+        let resolution = self.map_resolution.max(1);
+        let origin = coordinate.as_vec2() * self.tile_size;
+        let pixel_size = self.tile_size / resolution as f32;
+        let mut data = Vec::with_capacity((resolution * resolution) as usize);
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let position = origin + (Vec2::new(x as f32, y as f32) + 0.5) * pixel_size;
+                data.push((f(position) * 255.) as u8);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+/// Marks the entity the [`ProceduralGrass`] follows. Usually this is the camera.
+///
+/// If multiple anchors exist, only the first one is used
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct ProceduralGrassAnchor;
+
+/// A tile of the [`ProceduralGrass`] field.
+///
+/// Spawned and recycled by the [`ProceduralGrassPlugin`]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProceduralTile {
+    /// The coordinate of the tile on the x,z plane, in units of [`ProceduralGrass::tile_size`]
+    pub coordinate: IVec2,
+}
+
+/// Generates grass around the [`ProceduralGrassAnchor`] as defined in the [`ProceduralGrass`] resource.
+///
+/// Requires the [`WarblersPlugin`](crate::warblers_plugin::WarblersPlugin) to be added as well.
+/// The plugin does nothing as long as no [`ProceduralGrass`] resource exists
+pub struct ProceduralGrassPlugin;
+impl Plugin for ProceduralGrassPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_procedural_tiles
+                .run_if(resource_exists::<ProceduralGrass>)
+                .before(add_dither_task),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_procedural_tiles(
+    mut commands: Commands,
+    grass: Res<ProceduralGrass>,
+    anchor: Query<&GlobalTransform, With<ProceduralGrassAnchor>>,
+    mut tiles: Query<(
+        Entity,
+        &mut ProceduralTile,
+        &mut Transform,
+        &mut DensityMap,
+        &YMap,
+    )>,
+    mut images: ResMut<Assets<Image>>,
+    mut last_center: Local<Option<IVec2>>,
+) {
+    let Some(anchor) = anchor.iter().next() else {
+        return;
+    };
+    let center = grass.tile_coordinate(anchor.translation());
+    if *last_center == Some(center) && !grass.is_changed() {
+        return;
+    }
+    *last_center = Some(center);
+
+    let radius = grass.radius as i32;
+    let mut missing: HashSet<IVec2> = HashSet::new();
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            missing.insert(center + IVec2::new(x, z));
+        }
+    }
+    // tiles that are still in range don't have to be touched, unless the field itself changed
+    let regenerate_all = grass.is_changed();
+    let mut free = Vec::new();
+    for (entity, tile, ..) in &tiles {
+        if regenerate_all || !missing.remove(&tile.coordinate) {
+            free.push(entity);
+        }
+    }
+    for coordinate in missing {
+        let translation = Vec3::new(
+            coordinate.x as f32 * grass.tile_size,
+            0.,
+            coordinate.y as f32 * grass.tile_size,
+        );
+        if let Some(entity) = free.pop() {
+            // recycle a tile that left the ring
+            let Ok((_, mut tile, mut transform, mut density_map, y_map)) = tiles.get_mut(entity)
+            else {
+                continue;
+            };
+            tile.coordinate = coordinate;
+            transform.translation = translation;
+            if let Some(image) = images.get_mut(&density_map.density_map) {
+                *image = grass.density_image(coordinate);
+            }
+            if let Some(image) = images.get_mut(&y_map.y_map) {
+                *image = grass.y_image(coordinate);
+            }
+            // the maps changed, so the blades have to be dithered again
+            density_map.density = grass.density;
+            density_map.set_changed();
+            commands
+                .entity(entity)
+                .insert((
+                    Aabb::from_min_max(
+                        Vec3::ZERO,
+                        Vec3::new(grass.tile_size, grass.max_y, grass.tile_size),
+                    ),
+                    WarblerHeight::Uniform(grass.height),
+                    grass.grass_color.clone(),
+                    grass.grass_mesh.clone(),
+                ))
+                // the old blades would be drawn at the new position until the dithering finished
+                .remove::<Handle<DitheredBuffer>>();
+        } else {
+            let density_map =
+                DensityMap::new(images.add(grass.density_image(coordinate)), grass.density);
+            let y_map = YMap::new(images.add(grass.y_image(coordinate)));
+            commands.spawn((
+                WarblersBundle {
+                    grass_mesh: grass.grass_mesh.clone(),
+                    y_map,
+                    density_map,
+                    height: WarblerHeight::Uniform(grass.height),
+                    grass_color: grass.grass_color.clone(),
+                    aabb: Aabb::from_min_max(
+                        Vec3::ZERO,
+                        Vec3::new(grass.tile_size, grass.max_y, grass.tile_size),
+                    ),
+                    spatial: SpatialBundle::from_transform(Transform::from_translation(
+                        translation,
+                    )),
+                    ..default()
+                },
+                ProceduralTile { coordinate },
+            ));
+        }
+    }
+    // the ring got smaller
+    for entity in free {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{IVec2, Vec2, Vec3};
+
+    use super::{GrassGenerator, NoiseGenerator, ProceduralGrass};
+
+    #[test]
+    fn noise_is_deterministic() {
+        let generator = NoiseGenerator::default();
+        for position in [Vec2::ZERO, Vec2::new(-13.5, 7.25), Vec2::new(1e4, -1e4)] {
+            let density = generator.density(position);
+            assert_eq!(density, generator.density(position));
+            assert!((0. ..=1.).contains(&density));
+        }
+        let other = NoiseGenerator {
+            seed: 1,
+            ..Default::default()
+        };
+        assert_ne!(
+            generator.density(Vec2::new(3.3, 4.4)),
+            other.density(Vec2::new(3.3, 4.4))
+        );
+    }
+    #[test]
+    fn tile_coordinates() {
+        let grass = ProceduralGrass {
+            tile_size: 10.,
+            ..Default::default()
+        };
+        assert_eq!(grass.tile_coordinate(Vec3::new(5., 3., 5.)), IVec2::ZERO);
+        assert_eq!(
+            grass.tile_coordinate(Vec3::new(-0.5, 0., 25.)),
+            IVec2::new(-1, 2)
+        );
+    }
+    #[test]
+    fn tiles_match_world_position() {
+        let grass = ProceduralGrass::new(|position: Vec2| if position.x < 0. { 0. } else { 1. });
+        let left = grass.density_image(IVec2::new(-1, 0));
+        let right = grass.density_image(IVec2::new(0, 0));
+        assert!(left.data.iter().all(|pixel| *pixel == 0));
+        assert!(right.data.iter().all(|pixel| *pixel == 255));
+    }
+}
@@ -4,7 +4,7 @@ use bevy::{
 };
 
 use self::draw::{
-    SetColorBindGroup, SetHeightBindGroup, SetInstanceIndexBindGroup, SetNormalBindGroup,
+    SetColorBindGroup, SetGrassMaterialBindGroup, SetHeightBindGroup, SetInstanceIndexBindGroup,
     SetUniformBindGroup, SetVertexBuffer, SetYBindGroup,
 };
 
@@ -30,11 +30,25 @@ pub(crate) type GrassDrawCall = (
     SetColorBindGroup<3>,
     // Binds the [`GrassConfiguration`](crate::GrassConfiguration)
     SetUniformBindGroup<5>,
-    // Bind group for the y position lookup of the blades
+    // Bind group for the y position lookup and the normals of the blades
     SetYBindGroup<4>,
-    // Binds the normals of all the grass blades
-    SetNormalBindGroup<6>,
-    SetInstanceIndexBindGroup<7>,
+    SetInstanceIndexBindGroup<6>,
     // Binds the xz position of the grass instances to the vertex buffer
     SetVertexBuffer,
 );
+
+// The render call used for grass drawn with a [`GrassMaterial`](crate::material::GrassMaterial).
+// Equal to the [`GrassDrawCall`], but additionally binds the material
+pub(crate) type DrawGrassMaterial<M> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetHeightBindGroup<2>,
+    SetColorBindGroup<3>,
+    SetYBindGroup<4>,
+    SetUniformBindGroup<5>,
+    SetInstanceIndexBindGroup<6>,
+    // Binds the bind group of the material
+    SetGrassMaterialBindGroup<M, 7>,
+    SetVertexBuffer,
+);
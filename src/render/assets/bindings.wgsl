@@ -0,0 +1,43 @@
+#define_import_path warbler_grass::bindings
+
+#import warbler_grass::types::{
+    Color, ShaderAabb, ShaderHeightUniform, ShaderRegionConfiguration, Displacers, WindZones,
+    InstanceIndex,
+}
+
+#ifdef HEIGHT_TEXTURE
+    @group(2) @binding(0)
+    var height_texture: texture_2d<f32>;
+#else
+    @group(2) @binding(0)
+    var<uniform> height_uniform: ShaderHeightUniform;
+#endif
+@group(3) @binding(0)
+var<uniform> color: Color;
+@group(3) @binding(1)
+var albedo_texture: texture_2d<f32>;
+@group(3) @binding(2)
+var albedo_sampler: sampler;
+
+@group(4) @binding(0)
+var y_texture: texture_2d<f32>;
+@group(4) @binding(1)
+var<uniform> aabb: ShaderAabb;
+@group(4) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(4) @binding(3)
+var color_texture: texture_2d<f32>;
+
+@group(5) @binding(0)
+var<uniform> config: ShaderRegionConfiguration;
+@group(5) @binding(1)
+var noise_texture: texture_2d<f32>;
+@group(5) @binding(2)
+var<uniform> displacers: Displacers;
+@group(5) @binding(3)
+var trail_texture: texture_2d<f32>;
+@group(5) @binding(4)
+var<uniform> wind_zones: WindZones;
+
+@group(6) @binding(0)
+var<uniform> instance_index: InstanceIndex;
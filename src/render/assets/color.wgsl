@@ -0,0 +1,12 @@
+#define_import_path warbler_grass::color
+
+#import warbler_grass::bindings::{color as color_uniform, color_texture}
+#import warbler_grass::utils::texture2d_offset
+
+// The color stage of the grass shader.
+// `vertex_position` is the position of the vertex in the blade mesh
+fn color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32> {
+    let lambda = clamp(vertex_position.y, 0., 1.);
+    let tint = vec4<f32>(texture2d_offset(color_texture, field_position), 1.);
+    return mix(color_uniform.bottom_color, color_uniform.main_color, lambda) * tint;
+}
@@ -0,0 +1,31 @@
+#define_import_path warbler_grass::displacement
+
+#import warbler_grass::bindings::{displacers, trail_texture}
+
+// Returns the offset on the x,z plane in world space, that pushes the blade away from all displacers
+fn displacement_offset(world_position: vec3<f32>) -> vec2<f32> {
+    var offset = vec2<f32>(0.);
+    for (var i = 0u; i < displacers.count; i++) {
+        let displacer = displacers.displacers[i];
+        let radius = displacer.position_radius.w;
+        let difference = world_position - displacer.position_radius.xyz;
+        let distance = length(difference);
+        if distance < radius && length(difference.xz) > 0.0001 {
+            let strength = pow(1. - distance / radius, displacer.strength_falloff.y);
+            offset += normalize(difference.xz) * strength * displacer.strength_falloff.x;
+        }
+    }
+    if displacers.trail_enabled == 1u {
+        let uv = (world_position.xz - displacers.trail_area.xy) / displacers.trail_area.zw;
+        if all(uv >= vec2<f32>(0.)) && all(uv < vec2<f32>(1.)) {
+            let dim = vec2<f32>(textureDimensions(trail_texture, 0));
+            let trail = textureLoad(trail_texture, vec2<i32>(uv * dim), 0);
+            let trail_offset = (trail.xy * 2. - vec2<f32>(1.)) * trail.z;
+            // the trail shouldn't add up with the displacer that is currently creating it
+            if length(trail_offset) > length(offset) {
+                offset = trail_offset;
+            }
+        }
+    }
+    return offset;
+}
@@ -1,117 +1,77 @@
-#import bevy_pbr::mesh_functions::{mesh_position_local_to_clip, get_model_matrix}
-#import bevy_pbr::mesh_types::Mesh
-#import bevy_pbr::mesh_bindings::mesh
-#import bevy_render::maths::affine_to_square
-
-struct ShaderRegionConfiguration {
-    time: f32,
-    _wasm_padding: f32,
-    wind: vec2<f32>,
-}
-struct Vertex {
-    @location(0) vertex_position: vec3<f32>,
-    @location(3) xz_position: vec2<f32>,
+#import bevy_pbr::mesh_functions::{mesh_position_local_to_clip, mesh_normal_local_to_world, get_model_matrix}
+#import bevy_pbr::mesh_view_bindings::{lights, view, fog}
+#import bevy_pbr::mesh_view_types::FOG_MODE_OFF
+#import bevy_pbr::pbr_functions::apply_fog
+#import warbler_grass::types::{Vertex, VertexOutput}
+#import warbler_grass::bindings::{config, instance_index, color as color_uniform, albedo_texture, albedo_sampler}
+#import warbler_grass::utils::density_map_offset
+#import warbler_grass::displacement::displacement_offset
+
+// Every stage can be replaced by a hook, which is selected by the corresponding shader def.
+// The hook modules always exist, without a registered hook they use the built-in stage
+#import warbler_grass::wind::{wind as builtin_wind}
+#import warbler_grass::y_map::{y_position as builtin_y_position}
+#import warbler_grass::normal::{alignment as builtin_alignment}
+#import warbler_grass::height::{height as builtin_height}
+#import warbler_grass::color::{color as builtin_color}
+#import warbler_grass::hooks::wind_hook::{wind as hook_wind}
+#import warbler_grass::hooks::y_map_hook::{y_position as hook_y_position}
+#import warbler_grass::hooks::normal_hook::{alignment as hook_alignment}
+#import warbler_grass::hooks::height_hook::{height as hook_height}
+#import warbler_grass::hooks::color_hook::{color as hook_color}
+
+fn stage_wind(world_root: vec3<f32>, field_position: vec2<f32>, local_wind: vec2<f32>) -> vec2<f32> {
+#ifdef WIND_HOOK
+    return hook_wind(world_root, field_position, local_wind);
+#else
+    return builtin_wind(world_root, field_position, local_wind);
+#endif
 }
-struct Color {
-    main_color: vec4<f32>,
-    bottom_color: vec4<f32>,
+fn stage_y_position(field_position: vec2<f32>) -> f32 {
+#ifdef Y_MAP_HOOK
+    return hook_y_position(field_position);
+#else
+    return builtin_y_position(field_position);
+#endif
 }
-struct ShaderAabb {
-    vect: vec3<f32>,
-    _wasm_padding: f32,
+fn stage_alignment(field_position: vec2<f32>) -> mat3x3<f32> {
+#ifdef NORMAL_HOOK
+    return hook_alignment(field_position);
+#else
+    return builtin_alignment(field_position);
+#endif
 }
-
-struct InstanceIndex {
-    index: u32,
-    // We have to respect the memory layout here
-    _padding1: u32,
-    _padding2: vec2u,
+fn stage_height(field_position: vec2<f32>) -> f32 {
+#ifdef HEIGHT_HOOK
+    return hook_height(field_position);
+#else
+    return builtin_height(field_position);
+#endif
 }
-#ifdef HEIGHT_TEXTURE
-    @group(2) @binding(0)
-    var height_texture: texture_2d<f32>;
+fn stage_color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32> {
+#ifdef COLOR_HOOK
+    return hook_color(vertex_position, field_position);
 #else
-    struct ShaderHeightUniform {
-        height: f32,
-        _wasm_padding: vec2<f32>,
-    }
-    @group(2) @binding(0)
-    var<uniform> height_uniform: ShaderHeightUniform;
+    return builtin_color(vertex_position, field_position);
 #endif
-@group(3) @binding(0)
-var<uniform> color: Color;
-
-
-@group(4) @binding(0)
-var y_texture: texture_2d<f32>;
-@group(4) @binding(1)
-var<uniform> aabb: ShaderAabb;
-
-@group(5) @binding(0)
-var<uniform> config: ShaderRegionConfiguration;
-@group(5) @binding(1)
-var noise_texture: texture_2d<f32>;
-
-@group(6) @binding(0)
-var t_normal: texture_2d<f32>;
-
-@group(7) @binding(0)
-var<uniform> instance_index: InstanceIndex;
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec4<f32>,
-};
-
-const NOISE_TEXTURE_SPEED: f32 = 50.;
-const NOISE_TEXTURE_ZOOM: f32 = 35.;
-fn wind_offset(vertex_position: vec2<f32>) -> vec2<f32> {
-    var texture_offset = config.wind.xy * config.time * NOISE_TEXTURE_SPEED;
-    var texture_position = vec2<f32>(vertex_position.x ,vertex_position.y) * NOISE_TEXTURE_ZOOM + texture_offset;
-    
-    // dimensions of noise texture in vec2<u32>
-    let dim = textureDimensions(noise_texture, 0);
-
-    // read just position in case of a over/under flow of tex. coords
-    texture_position = abs(texture_position % vec2<f32>(dim));
-    var texture_pixel = textureLoad(noise_texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0);
-    return texture_pixel.xy * config.wind;
 }
-const BIG_PRIME: f32 = 1302151.;
-
-fn density_map_offset(vertex_position: vec2<f32>) -> vec2<f32> {
-    var texture_position = vec2<f32>(vertex_position.x ,vertex_position.y) * BIG_PRIME ;
-    
-    // dimensions of noise texture in vec2<u32>
-    let dim = textureDimensions(noise_texture, 0);
 
-    // read just position in case of a over/under flow of tex. coords
-    texture_position = abs(texture_position % vec2<f32>(dim));
-    var texture_pixel = textureLoad(noise_texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0);
-    return texture_pixel.xz - vec2<f32>(0.5,0.5) ;
-}
-fn texture2d_offset(texture: texture_2d<f32>, vertex_position: vec2<f32>) -> vec3<f32> {
-    let dim = textureDimensions(texture, 0);
-let texture_position = abs((vertex_position.xy / aabb.vect.xz ) * vec2<f32>(dim)) ;
-    var texture_rgb = textureLoad(texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0).rgb;
-    return texture_rgb;
+// Returns the rotation around the up axis, which turns the z axis of the blade towards the camera
+fn billboard(model: mat4x4<f32>, world_root: vec3<f32>) -> mat3x3<f32> {
+    // the transpose only inverts the rotation of the chunk, which is enough for a direction
+    let local_model = mat3x3<f32>(model[0].xyz, model[1].xyz, model[2].xyz);
+    let to_camera = (transpose(local_model) * (view.world_position - world_root)).xz;
+    if length(to_camera) < 0.0001 {
+        return mat3x3<f32>(vec3<f32>(1., 0., 0.), vec3<f32>(0., 1., 0.), vec3<f32>(0., 0., 1.));
+    }
+    let facing = normalize(to_camera);
+    return mat3x3<f32>(
+        vec3<f32>(facing.y, 0., -facing.x),
+        vec3<f32>(0., 1., 0.),
+        vec3<f32>(facing.x, 0., facing.y),
+    );
 }
-// Source: https://gist.github.com/kevinmoran/b45980723e53edeb8a5a43c49f134724
-// Returns a rotation matrix that aligns v1 with v2
-fn rotate_align(v1: vec3<f32>, v2: vec3<f32>) -> mat3x3<f32> {
-    let axis = cross(v1, v2);
-
-    let cos_a = dot(v1, v2);
-    let k = 1.0 / (1.0 + cos_a);
 
-    let result = mat3x3f( 
-            (axis.x * axis.x * k) + cos_a, (axis.x * axis.y * k) + axis.z, (axis.x * axis.z * k) - axis.y,
-            (axis.y * axis.x * k) - axis.z, (axis.y * axis.y * k) + cos_a,  (axis.y * axis.z * k) + axis.x, 
-            (axis.z * axis.x * k) + axis.y, (axis.z * axis.y * k) - axis.x, (axis.z * axis.z * k) + cos_a 
-        );
-
-    return result;
-}
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
@@ -122,41 +82,105 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     position_field_offset += vec3<f32>(density_offset.x, 0., density_offset.y);
 
     // ---Y_POSITIONS---
-    position_field_offset.y = texture2d_offset(y_texture, position_field_offset.xz).r * aabb.vect.y;
+    position_field_offset.y = stage_y_position(position_field_offset.xz);
     
     // ---NORMAL---
-    var normal = sqrt(texture2d_offset(t_normal, vertex.xz_position.xy).xyz); // Get normal scaled over grass field in linear space
-    normal = normal * 2. - vec3f(1.);
-    normal = normalize(normal);
-    let rotation_matrix = rotate_align(vec3<f32>(0.0, 1.0, 0.0), normal); // Calculate rotation matrix to align grass with normal
+    var rotation_matrix = stage_alignment(vertex.xz_position.xy);
+    let model = get_model_matrix(instance_index.index);
+    let world_root = (model * vec4<f32>(position_field_offset, 1.0)).xyz;
+#ifdef BILLBOARD
+    // ---BILLBOARD---
+    // turn the blade towards the camera before aligning it with the slope
+    rotation_matrix = rotation_matrix * billboard(model, world_root);
+#endif
     
     // ---HEIGHT---
-    var height = 0.;
-    #ifdef HEIGHT_TEXTURE
-        height = (texture2d_offset(height_texture, position_field_offset.xz).r + 4.) / 3.;
-    #else
-        height = height_uniform.height;
+    let height = stage_height(position_field_offset.xz);
+    var vertex_position = vertex.vertex_position * vec3<f32>(1., height, 1.);
+    #ifdef BLADE_STATES
+        // ---BLADE_STATE---
+        vertex_position *= vertex.blade_state.x;
+        // flattened blades lean into a random direction
+        let lean = normalize(density_offset + vec2<f32>(0.001, 0.));
+        vertex_position.x += vertex_position.y * lean.x * vertex.blade_state.y;
+        vertex_position.z += vertex_position.y * lean.y * vertex.blade_state.y;
+        vertex_position.y *= 1. - 0.9 * vertex.blade_state.y;
     #endif
-    var position = rotation_matrix * (vertex.vertex_position * vec3<f32>(1., height, 1.)) + position_field_offset;
+    var position = rotation_matrix * vertex_position + position_field_offset;
     // ---WIND---
     // only applies wind if the vertex is not on the bottom of the grass (or very small)
-    let offset = wind_offset(position_field_offset.xz);
-    let strength = max(0.,log(vertex.vertex_position.y + 1.));
+    var local_wind = config.wind;
+    if instance_index.has_wind_override == 1u {
+        local_wind = instance_index.wind_override;
+    }
+    let offset = stage_wind(world_root, position_field_offset.xz, local_wind);
+    var strength = max(0.,log(vertex.vertex_position.y + 1.));
+    #ifdef BLADE_STATES
+        strength *= vertex.blade_state.x * (1. - vertex.blade_state.y);
+    #endif
     position.x += offset.x * strength;
     position.z += offset.y * strength;
+
+    // ---DISPLACEMENT---
+    let displacement = displacement_offset(world_root) * strength;
+    // bend the blade to the ground instead of only moving the tip
+    position.x += displacement.x;
+    position.z += displacement.y;
+    position.y -= min(length(displacement), max(position.y - position_field_offset.y, 0.)) * 0.5;
     
     // ---CLIP_POSITION---
-    out.clip_position = mesh_position_local_to_clip(get_model_matrix(instance_index.index), vec4<f32>(position, 1.0));
+    out.clip_position = mesh_position_local_to_clip(model, vec4<f32>(position, 1.0));
+    out.world_position = model * vec4<f32>(position, 1.0);
 
-    // ---COLOR---
-    var lambda = clamp(vertex.vertex_position.y, 0., 1.) ;
+    // ---WORLD_NORMAL---
+#ifdef VERTEX_NORMALS
+    let local_normal = rotation_matrix * vertex.normal;
+#else
+    let local_normal = rotation_matrix * vec3<f32>(0., 1., 0.);
+#endif
+    out.world_normal = mesh_normal_local_to_world(local_normal, instance_index.index);
 
-    out.color = mix(color.bottom_color, color.main_color, lambda) ;
+    // ---COLOR---
+    out.color = stage_color(vertex.vertex_position, position_field_offset.xz);
+#ifdef VERTEX_COLORS
+    out.color *= vertex.color;
+#endif
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#endif
     return out;
 }
 
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
+    var color = in.color;
+#ifdef DIFFUSE_LIGHTING
+    // simple diffuse lighting from the directional lights, lit from both sides since blades are thin
+    if lights.n_directional_lights > 0u {
+        let normal = normalize(in.world_normal);
+        var diffuse = 0.;
+        for (var i = 0u; i < lights.n_directional_lights; i++) {
+            diffuse = max(diffuse, abs(dot(normal, lights.directional_lights[i].direction_to_light)));
+        }
+        color = vec4<f32>(color.rgb * (0.5 + 0.5 * diffuse), color.a);
+    }
+#endif
+#ifdef ALBEDO_TEXTURE
+    color *= textureSample(albedo_texture, albedo_sampler, in.uv);
+#ifdef ALPHA_TO_COVERAGE
+    // sharpen the alpha around the cutoff, so that the coverage creates a crisp, anti-aliased edge
+    color.a = (color.a - color_uniform.alpha_cutoff) / max(fwidth(color.a), 0.0001) + 0.5;
+#else
+    if color.a < color_uniform.alpha_cutoff {
+        discard;
+    }
+    color.a = 1.;
+#endif
+#endif
+    // ---FOG---
+    if fog.mode != FOG_MODE_OFF {
+        color = apply_fog(fog, color, in.world_position.xyz, view.world_position.xyz);
+    }
+    return color;
 }
@@ -0,0 +1,18 @@
+#define_import_path warbler_grass::height
+
+#ifdef HEIGHT_TEXTURE
+#import warbler_grass::bindings::height_texture
+#import warbler_grass::utils::texture2d_offset
+#else
+#import warbler_grass::bindings::height_uniform
+#endif
+
+// The height stage of the grass shader.
+// Returns the factor the blade mesh is scaled with on the y axis
+fn height(field_position: vec2<f32>) -> f32 {
+#ifdef HEIGHT_TEXTURE
+    return (texture2d_offset(height_texture, field_position).r + 4.) / 3.;
+#else
+    return height_uniform.height;
+#endif
+}
@@ -0,0 +1,6 @@
+// The default color hook, which uses the built-in color stage
+#import warbler_grass::color::{color as builtin_color}
+
+fn color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32> {
+    return builtin_color(vertex_position, field_position);
+}
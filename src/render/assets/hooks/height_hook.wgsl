@@ -0,0 +1,6 @@
+// The default height hook, which uses the built-in height stage
+#import warbler_grass::height::{height as builtin_height}
+
+fn height(field_position: vec2<f32>) -> f32 {
+    return builtin_height(field_position);
+}
@@ -0,0 +1,6 @@
+// The default normal alignment hook, which uses the built-in normal alignment stage
+#import warbler_grass::normal::{alignment as builtin_alignment}
+
+fn alignment(field_position: vec2<f32>) -> mat3x3<f32> {
+    return builtin_alignment(field_position);
+}
@@ -0,0 +1,6 @@
+// The default wind hook, which uses the built-in wind stage
+#import warbler_grass::wind::{wind as builtin_wind}
+
+fn wind(world_root: vec3<f32>, field_position: vec2<f32>, local_wind: vec2<f32>) -> vec2<f32> {
+    return builtin_wind(world_root, field_position, local_wind);
+}
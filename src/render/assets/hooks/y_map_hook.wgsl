@@ -0,0 +1,6 @@
+// The default y-map hook, which uses the built-in y-map stage
+#import warbler_grass::y_map::{y_position as builtin_y_position}
+
+fn y_position(field_position: vec2<f32>) -> f32 {
+    return builtin_y_position(field_position);
+}
@@ -0,0 +1,29 @@
+#define_import_path warbler_grass::normal
+
+#import warbler_grass::bindings::t_normal
+#import warbler_grass::utils::texture2d_offset
+
+// The normal alignment stage of the grass shader.
+// Returns the rotation applied to the blade, aligning it with the normal map
+fn alignment(field_position: vec2<f32>) -> mat3x3<f32> {
+    var normal = sqrt(texture2d_offset(t_normal, field_position).xyz); // Get normal scaled over grass field in linear space
+    normal = normal * 2. - vec3f(1.);
+    normal = normalize(normal);
+    return rotate_align(vec3<f32>(0.0, 1.0, 0.0), normal); // Calculate rotation matrix to align grass with normal
+}
+// Source: https://gist.github.com/kevinmoran/b45980723e53edeb8a5a43c49f134724
+// Returns a rotation matrix that aligns v1 with v2
+fn rotate_align(v1: vec3<f32>, v2: vec3<f32>) -> mat3x3<f32> {
+    let axis = cross(v1, v2);
+
+    let cos_a = dot(v1, v2);
+    let k = 1.0 / (1.0 + cos_a);
+
+    let result = mat3x3f( 
+            (axis.x * axis.x * k) + cos_a, (axis.x * axis.y * k) + axis.z, (axis.x * axis.z * k) - axis.y,
+            (axis.y * axis.x * k) - axis.z, (axis.y * axis.y * k) + cos_a,  (axis.y * axis.z * k) + axis.x, 
+            (axis.z * axis.x * k) + axis.y, (axis.z * axis.y * k) - axis.x, (axis.z * axis.z * k) + cos_a 
+        );
+
+    return result;
+}
@@ -0,0 +1,94 @@
+#define_import_path warbler_grass::types
+
+struct ShaderRegionConfiguration {
+    // accumulated on the cpu and wrapped at the size of the noise texture
+    noise_offset: vec2<f32>,
+    gust_phase: f32,
+    wind_strength: f32,
+    wind: vec2<f32>,
+    wind_speed: f32,
+    wind_scale: f32,
+    gust_amplitude: f32,
+    phase_variation: f32,
+    gust_length: f32,
+    _wasm_padding: f32,
+}
+struct Vertex {
+    @location(0) vertex_position: vec3<f32>,
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+    @location(9) xz_position: vec2<f32>,
+#ifdef BLADE_STATES
+    // x: how much of the blade is left, y: how flat the blade lies on the ground
+    @location(8) blade_state: vec2<f32>,
+#endif
+}
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // the uv of the blade mesh, zero if the mesh has no uvs
+    @location(1) uv: vec2<f32>,
+    // the normal of the blade in world space, pointing up along the blade if the mesh has no normals
+    @location(2) world_normal: vec3<f32>,
+    // the position of the vertex in world space
+    @location(3) world_position: vec4<f32>,
+};
+struct Color {
+    main_color: vec4<f32>,
+    bottom_color: vec4<f32>,
+    alpha_cutoff: f32,
+    _wasm_padding: f32,
+    _wasm_padding_b: vec2<f32>,
+}
+struct ShaderAabb {
+    vect: vec3<f32>,
+    _wasm_padding: f32,
+}
+struct ShaderHeightUniform {
+    height: f32,
+    _wasm_padding: vec2<f32>,
+}
+
+struct Displacer {
+    // xyz: world position, w: radius
+    position_radius: vec4<f32>,
+    // x: strength, y: falloff
+    strength_falloff: vec4<f32>,
+}
+struct Displacers {
+    // xy: minimum of the trail area, zw: size of the trail area
+    trail_area: vec4<f32>,
+    count: u32,
+    trail_enabled: u32,
+    _wasm_padding: vec2<u32>,
+    displacers: array<Displacer, 16>,
+}
+
+struct WindZone {
+    // xyz: world position, w: radius
+    position_radius: vec4<f32>,
+    // x: kind (0: directional, 1: radial), y: falloff
+    // zw: wind of directional zones, z: strength of radial zones
+    params: vec4<f32>,
+}
+struct WindZones {
+    count: u32,
+    // a vec3 would be aligned to 16 bytes
+    _wasm_padding: u32,
+    _wasm_padding_b: vec2<u32>,
+    zones: array<WindZone, 8>,
+}
+
+struct InstanceIndex {
+    index: u32,
+    // 1 if the chunk overrides the global wind
+    has_wind_override: u32,
+    wind_override: vec2<f32>,
+}
@@ -0,0 +1,25 @@
+#define_import_path warbler_grass::utils
+
+#import warbler_grass::bindings::{noise_texture, aabb}
+
+const BIG_PRIME: f32 = 1302151.;
+
+// Returns a random offset in the range [-0.5, 0.5] for the blade at the position
+fn density_map_offset(vertex_position: vec2<f32>) -> vec2<f32> {
+    var texture_position = vec2<f32>(vertex_position.x ,vertex_position.y) * BIG_PRIME ;
+    
+    // dimensions of noise texture in vec2<u32>
+    let dim = textureDimensions(noise_texture, 0);
+
+    // read just position in case of a over/under flow of tex. coords
+    texture_position = abs(texture_position % vec2<f32>(dim));
+    var texture_pixel = textureLoad(noise_texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0);
+    return texture_pixel.xz - vec2<f32>(0.5,0.5) ;
+}
+// Samples a texture stretched over the aabb of the chunk
+fn texture2d_offset(texture: texture_2d<f32>, vertex_position: vec2<f32>) -> vec3<f32> {
+    let dim = textureDimensions(texture, 0);
+    let texture_position = abs((vertex_position.xy / aabb.vect.xz ) * vec2<f32>(dim)) ;
+    var texture_rgb = textureLoad(texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0).rgb;
+    return texture_rgb;
+}
@@ -0,0 +1,69 @@
+#define_import_path warbler_grass::wind
+
+#import warbler_grass::bindings::{config, noise_texture, wind_zones}
+#import warbler_grass::types::WindZone
+#import warbler_grass::utils::density_map_offset
+
+const TAU: f32 = 6.28318530718;
+
+// The wind stage of the grass shader.
+// Returns the offset of the blade tip on the x,z plane.
+// `wind` is the wind of the chunk, which might differ from the global wind in the configuration
+fn wind(world_root: vec3<f32>, field_position: vec2<f32>, wind: vec2<f32>) -> vec2<f32> {
+    let local_wind = zone_wind(world_root, wind);
+    return wind_offset(field_position, local_wind) + radial_wind_offset(world_root);
+}
+// `wind` is the local wind, which might differ from the global wind in the configuration
+fn wind_offset(vertex_position: vec2<f32>, wind: vec2<f32>) -> vec2<f32> {
+    var direction = vec2<f32>(0.);
+    if any(config.wind != vec2<f32>(0.)) {
+        direction = normalize(config.wind);
+    }
+    // every blade is shifted by a random time, so that neighbouring blades don't move in sync
+    let phase_shift = density_map_offset(vertex_position).x * config.phase_variation;
+    var texture_offset = config.noise_offset + direction * phase_shift * config.wind_speed;
+    var texture_position = vec2<f32>(vertex_position.x ,vertex_position.y) * config.wind_scale + texture_offset;
+    
+    // dimensions of noise texture in vec2<u32>
+    let dim = vec2<f32>(textureDimensions(noise_texture, 0));
+
+    // wrap the position, the noise texture is tileable
+    texture_position = min(texture_position - floor(texture_position / dim) * dim, dim - vec2<f32>(1.));
+    var texture_pixel = textureLoad(noise_texture, vec2<i32>(i32(texture_position.x),i32(texture_position.y)), 0);
+
+    // gusts travel over the field in the direction of the wind
+    let gust_wave = sin(TAU * (config.gust_phase - dot(vertex_position, direction) / config.gust_length));
+    let gust = 1. + config.gust_amplitude * max(gust_wave, 0.);
+    return texture_pixel.xy * wind * config.wind_strength * gust;
+}
+fn wind_zone_weight(zone: WindZone, world_position: vec3<f32>) -> f32 {
+    let radius = zone.position_radius.w;
+    let distance = distance(world_position, zone.position_radius.xyz);
+    if distance >= radius {
+        return 0.;
+    }
+    return pow(1. - distance / radius, zone.params.y);
+}
+// Blends the wind with all directional wind zones
+fn zone_wind(world_position: vec3<f32>, wind: vec2<f32>) -> vec2<f32> {
+    var result = wind;
+    for (var i = 0u; i < wind_zones.count; i++) {
+        let zone = wind_zones.zones[i];
+        if zone.params.x == 0. {
+            result = mix(result, zone.params.zw, wind_zone_weight(zone, world_position));
+        }
+    }
+    return result;
+}
+// Returns the offset on the x,z plane in world space, caused by radial wind zones
+fn radial_wind_offset(world_position: vec3<f32>) -> vec2<f32> {
+    var offset = vec2<f32>(0.);
+    for (var i = 0u; i < wind_zones.count; i++) {
+        let zone = wind_zones.zones[i];
+        let difference = world_position.xz - zone.position_radius.xz;
+        if zone.params.x == 1. && length(difference) > 0.0001 {
+            offset += normalize(difference) * zone.params.z * wind_zone_weight(zone, world_position);
+        }
+    }
+    return offset;
+}
@@ -0,0 +1,10 @@
+#define_import_path warbler_grass::y_map
+
+#import warbler_grass::bindings::{y_texture, aabb}
+#import warbler_grass::utils::texture2d_offset
+
+// The y-map stage of the grass shader.
+// Returns the y position of the blade root in the local space of the chunk
+fn y_position(field_position: vec2<f32>) -> f32 {
+    return texture2d_offset(y_texture, field_position).r * aabb.vect.y;
+}
@@ -1,4 +1,13 @@
-use bevy::{prelude::*, render::render_resource::BindGroup};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{BindGroup, Buffer},
+    utils::HashMap,
+};
 
 #[derive(Resource, Default)]
 pub(crate) struct UniformBuffer(Option<BindGroup>);
@@ -10,3 +19,49 @@ impl UniformBuffer {
         self.0.as_ref().unwrap()
     }
 }
+
+/// The gpu buffers of the [`GrassBladeStates`](crate::cutting::GrassBladeStates) of each chunk.
+///
+/// Kept alive between frames, so that only changed states have to be uploaded
+#[derive(Resource, Default)]
+pub(crate) struct GpuBladeStates(pub HashMap<Entity, GpuBladeState>);
+pub(crate) struct GpuBladeState {
+    pub buffer: Buffer,
+    pub len: usize,
+}
+/// The mesh uniform index and wind override of the chunks, for all views in a single buffer
+#[derive(Resource, Default)]
+pub(crate) struct InstanceIndexBuffer {
+    /// The entries of the buffer, aligned for dynamic offsets
+    pub data: Vec<u8>,
+    pub buffer: Option<Buffer>,
+    pub bind_group: Option<BindGroup>,
+    /// The offset into the buffer of each (view, chunk) pair
+    pub offsets: HashMap<(Entity, Entity), u32>,
+}
+
+/// Counts the grass draw calls of the render world.
+///
+/// The same counters are inserted in the main world, where they are read by the
+/// [`WarblerDiagnosticsPlugin`](crate::diagnostic::WarblerDiagnosticsPlugin)
+#[derive(Resource, Clone, Default)]
+pub(crate) struct GrassDrawCalls(Arc<DrawCallCounters>);
+#[derive(Default)]
+struct DrawCallCounters {
+    current: AtomicU32,
+    last_frame: AtomicU32,
+}
+impl GrassDrawCalls {
+    pub fn add(&self) {
+        self.0.current.fetch_add(1, Ordering::Relaxed);
+    }
+    /// The draw calls of the last rendered frame
+    pub fn last_frame(&self) -> u32 {
+        self.0.last_frame.load(Ordering::Relaxed)
+    }
+    /// Stores the draw calls of the finished frame and starts counting again
+    pub fn finish_frame(draw_calls: Res<GrassDrawCalls>) {
+        let finished = draw_calls.0.current.swap(0, Ordering::Relaxed);
+        draw_calls.0.last_frame.store(finished, Ordering::Relaxed);
+    }
+}
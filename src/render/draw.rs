@@ -1,3 +1,7 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
+
 use bevy::{
     ecs::{
         query::ROQueryItem,
@@ -9,21 +13,25 @@ use bevy::{
     pbr::RenderMeshInstances,
     prelude::*,
     render::{
-        mesh::GpuBufferInfo,
+        mesh::{GpuBufferInfo, GpuMesh},
         render_asset::RenderAssets,
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        view::ExtractedView,
     },
 };
 
 use crate::{
-    dithering::DitheredBuffer,
+    cutting::BladeState,
+    dithering::{DitheredBuffer, GpuDitheredBuffer},
     map::YMap,
-    prelude::{GrassColor, NormalMap, WarblerHeight},
+    material::{GrassMaterial, RenderGrassMaterials},
+    prelude::{GrassColor, GrassLods, WarblerHeight},
 };
 
 use super::{
-    cache::UniformBuffer,
-    prepare::{BindGroupBuffer, IndexBindgroup},
+    cache::{GpuBladeStates, GrassDrawCalls, InstanceIndexBuffer, UniformBuffer},
+    extract::ExtractedBladeStates,
+    prepare::BindGroupBuffer,
 };
 pub(crate) struct SetUniformBindGroup<const I: usize>;
 
@@ -66,28 +74,6 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetYBindGroup<I> {
         }
     }
 }
-pub(crate) struct SetNormalBindGroup<const I: usize>;
-
-impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetNormalBindGroup<I> {
-    type Param = ();
-    type ViewQuery = ();
-    type ItemQuery = Read<BindGroupBuffer<NormalMap>>;
-
-    fn render<'w>(
-        _item: &P,
-        _view: ROQueryItem<'w, Self::ViewQuery>,
-        bind_group: Option<&'w BindGroupBuffer<NormalMap>>,
-        _cache: SystemParamItem<'w, '_, Self::Param>,
-        pass: &mut TrackedRenderPass<'w>,
-    ) -> RenderCommandResult {
-        if let Some(bind_group) = bind_group {
-            pass.set_bind_group(I, &bind_group.bind_group, &[]);
-            RenderCommandResult::Success
-        } else {
-            RenderCommandResult::Failure
-        }
-    }
-}
 pub(crate) struct SetColorBindGroup<const I: usize>;
 
 impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetColorBindGroup<I> {
@@ -135,25 +121,51 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetHeightBindGroup<I> {
 pub(crate) struct SetInstanceIndexBindGroup<const N: usize>;
 
 impl<P: PhaseItem, const N: usize> RenderCommand<P> for SetInstanceIndexBindGroup<N> {
-    type Param = ();
-    type ViewQuery = ();
-
-    type ItemQuery = Read<IndexBindgroup>;
+    type Param = SRes<InstanceIndexBuffer>;
+    type ViewQuery = Entity;
+    type ItemQuery = ();
 
     #[inline]
+    fn render<'w>(
+        item: &P,
+        view: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<()>,
+        instance_indices: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let instance_indices = instance_indices.into_inner();
+        let (Some(bind_group), Some(offset)) = (
+            &instance_indices.bind_group,
+            instance_indices.offsets.get(&(view, item.entity())),
+        ) else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(N, bind_group, &[*offset]);
+        RenderCommandResult::Success
+    }
+}
+pub(crate) struct SetGrassMaterialBindGroup<M: GrassMaterial, const I: usize>(PhantomData<M>);
+
+impl<P: PhaseItem, M: GrassMaterial, const I: usize> RenderCommand<P>
+    for SetGrassMaterialBindGroup<M, I>
+{
+    type Param = SRes<RenderGrassMaterials<M>>;
+    type ViewQuery = ();
+    type ItemQuery = Read<Handle<M>>;
+
     fn render<'w>(
         _item: &P,
         _view: ROQueryItem<'w, Self::ViewQuery>,
-        index_bindgroup: Option<&'w IndexBindgroup>,
-        _: SystemParamItem<'w, '_, Self::Param>,
+        material: Option<&'w Handle<M>>,
+        materials: SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        if let Some(index_bindgroup) = index_bindgroup {
-            pass.set_bind_group(N, &index_bindgroup.bind_group, &[]);
-            RenderCommandResult::Success
-        } else {
-            RenderCommandResult::Failure
-        }
+        let Some(material) = material.and_then(|handle| materials.into_inner().get(&handle.id()))
+        else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, &material.bind_group, &[]);
+        RenderCommandResult::Success
     }
 }
 pub(crate) struct SetVertexBuffer;
@@ -163,53 +175,142 @@ impl<P: PhaseItem> RenderCommand<P> for SetVertexBuffer {
         SRes<RenderAssets<Mesh>>,
         SRes<RenderMeshInstances>,
         SRes<RenderAssets<DitheredBuffer>>,
+        SRes<GpuBladeStates>,
+        SRes<GrassDrawCalls>,
+    );
+    type ViewQuery = Read<ExtractedView>;
+    type ItemQuery = (
+        Read<Handle<DitheredBuffer>>,
+        Has<ExtractedBladeStates>,
+        Option<Read<GrassLods>>,
     );
-    type ViewQuery = ();
-    type ItemQuery = Read<Handle<DitheredBuffer>>;
 
     #[inline]
     fn render<'w>(
         item: &P,
-        _view: ROQueryItem<'w, Self::ViewQuery>,
-        dither_handle: Option<&'w Handle<DitheredBuffer>>,
-        (meshes, render_mesh_instances, dither): SystemParamItem<'w, '_, Self::Param>,
+        view: ROQueryItem<'w, Self::ViewQuery>,
+        entity: Option<(&'w Handle<DitheredBuffer>, bool, Option<&'w GrassLods>)>,
+        (meshes, render_mesh_instances, dither, blade_states, draw_calls): SystemParamItem<
+            'w,
+            '_,
+            Self::Param,
+        >,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
+        let Some((dither_handle, has_blade_states, lods)) = entity else {
+            return RenderCommandResult::Failure;
+        };
         let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
             return RenderCommandResult::Failure;
         };
-        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+        let meshes = meshes.into_inner();
+        let Some(gpu_mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
             return RenderCommandResult::Failure;
         };
-
-        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-        let blade_count;
-        if let Some(dither_handle) = dither_handle {
-            if let Some(gpu_dither) = dither.into_inner().get(dither_handle) {
-                blade_count = gpu_dither.instances as u32;
-                if blade_count == 0 {
-                    return RenderCommandResult::Failure;
-                }
-                pass.set_vertex_buffer(1, gpu_dither.buffer.slice(..));
-            } else {
-                return RenderCommandResult::Failure;
-            }
-        } else {
+        let Some(gpu_dither) = dither.into_inner().get(dither_handle) else {
+            return RenderCommandResult::Failure;
+        };
+        let mut blade_count = gpu_dither.instances as u32;
+        if blade_count == 0 {
             return RenderCommandResult::Failure;
         }
-        match &gpu_mesh.buffer_info {
-            GpuBufferInfo::Indexed {
-                buffer,
-                index_format,
-                count,
-            } => {
-                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                pass.draw_indexed(0..*count, 0, 0..blade_count);
+        // the pipeline expects the blade states if the chunk has any
+        let mut blade_states_buffer = None;
+        if has_blade_states {
+            let Some(gpu_states) = blade_states.into_inner().0.get(&item.entity()) else {
+                return RenderCommandResult::Failure;
+            };
+            // the states might lag behind for a frame after the blades are dithered again
+            blade_count = blade_count.min(gpu_states.len as u32);
+            blade_states_buffer = Some(&gpu_states.buffer);
+        }
+
+        let runs = match lods {
+            Some(lods) if !lods.levels().is_empty() => {
+                let transform = &mesh_instance.transforms.transform;
+                let camera = view.transform.translation();
+                lod_runs(gpu_dither, |center| {
+                    let center = transform.matrix3 * Vec3::new(center.x, 0., center.y)
+                        + transform.translation;
+                    lod_mesh(meshes, gpu_mesh, lods, center.distance(camera))
+                })
+            }
+            _ => vec![(gpu_mesh, 0..blade_count)],
+        };
+        for (mesh, instances) in runs {
+            let instances = instances.start..instances.end.min(blade_count);
+            if instances.is_empty() {
+                continue;
             }
-            GpuBufferInfo::NonIndexed => {
-                pass.draw(0..gpu_mesh.vertex_count, 0..blade_count);
+            // the instance buffers are offset instead of using the first instance,
+            // which is not supported on all platforms
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            let offset = instances.start as u64;
+            pass.set_vertex_buffer(
+                1,
+                gpu_dither
+                    .buffer
+                    .slice(offset * mem::size_of::<Vec2>() as u64..),
+            );
+            if let Some(buffer) = blade_states_buffer {
+                pass.set_vertex_buffer(
+                    2,
+                    buffer.slice(offset * mem::size_of::<BladeState>() as u64..),
+                );
+            }
+            let instance_count = instances.end - instances.start;
+            draw_calls.add();
+            match &mesh.buffer_info {
+                GpuBufferInfo::Indexed {
+                    buffer,
+                    index_format,
+                    count,
+                } => {
+                    pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                    pass.draw_indexed(0..*count, 0, 0..instance_count);
+                }
+                GpuBufferInfo::NonIndexed => {
+                    pass.draw(0..mesh.vertex_count, 0..instance_count);
+                }
             }
         }
         RenderCommandResult::Success
     }
 }
+/// Splits the blades of a chunk into continuous ranges drawn with the same mesh
+fn lod_runs<'w>(
+    dither: &GpuDitheredBuffer,
+    mut mesh_of_cell: impl FnMut(Vec2) -> &'w GpuMesh,
+) -> Vec<(&'w GpuMesh, Range<u32>)> {
+    let mut runs: Vec<(&GpuMesh, Range<u32>)> = Vec::new();
+    for cell in &dither.cells {
+        let mesh = mesh_of_cell(cell.center);
+        match runs.last_mut() {
+            Some((last, instances))
+                if std::ptr::eq(*last, mesh) && instances.end == cell.instances.start =>
+            {
+                instances.end = cell.instances.end;
+            }
+            _ => runs.push((mesh, cell.instances.clone())),
+        }
+    }
+    runs
+}
+/// Returns the mesh of the lod level at the `distance`
+fn lod_mesh<'w>(
+    meshes: &'w RenderAssets<Mesh>,
+    chunk_mesh: &'w GpuMesh,
+    lods: &GrassLods,
+    distance: f32,
+) -> &'w GpuMesh {
+    let Some(mesh) = lods.mesh_at(distance).and_then(|handle| meshes.get(handle)) else {
+        // the mesh of the level might still be loading
+        return chunk_mesh;
+    };
+    // the pipeline is specialized for the vertex layout of the chunk mesh
+    if mesh.layout != chunk_mesh.layout {
+        warn_once!("The meshes of the `GrassLods` need to have the same vertex attributes as the grass mesh of the chunk");
+        return chunk_mesh;
+    }
+    mesh
+}
@@ -1,4 +1,12 @@
-use crate::{dithering::DitheredBuffer, map::YMap};
+use crate::{
+    cutting::{BladeState, GrassBladeStates},
+    displacement::{
+        ExtractedDisplacers, GrassDisplacer, GrassTrail, ShaderDisplacer, MAX_DISPLACERS,
+    },
+    dithering::DitheredBuffer,
+    map::YMap,
+    wind::{ExtractedWindZones, ShaderWindZone, WindZone, MAX_WIND_ZONES},
+};
 use bevy::{
     prelude::*,
     render::{primitives::Aabb, Extract},
@@ -30,8 +38,114 @@ pub(crate) fn extract_aabb(
     }
     commands.insert_or_spawn_batch(values);
 }
-// # TODO
-// this is only a temporary solution. Since globals are not visible in the vertex shader in bevy version 0.13, we have to manually extract the time
-pub(crate) fn extract_time(mut commands: Commands, time: Extract<Res<Time>>) {
-    commands.insert_resource(**time);
+/// The blade states of a chunk in the render world
+#[derive(Component)]
+pub(crate) struct ExtractedBladeStates {
+    /// The states that changed since the last frame
+    pub update: Option<BladeStateUpdate>,
+}
+pub(crate) enum BladeStateUpdate {
+    /// The whole buffer has to be created again
+    Full(Vec<BladeState>),
+    /// Only the states starting at `offset` changed
+    Partial {
+        offset: usize,
+        states: Vec<BladeState>,
+    },
+}
+/// Extracts the [`GrassBladeStates`] of all chunks.
+///
+/// Only the states that changed since the last frame are copied into the render world
+pub(crate) fn extract_blade_states(
+    mut commands: Commands,
+    chunks: Extract<Query<(Entity, &GrassBladeStates)>>,
+) {
+    let mut values = Vec::new();
+    for (entity, states) in chunks.iter() {
+        let update = if states.resized {
+            Some(BladeStateUpdate::Full(states.states().to_vec()))
+        } else {
+            states
+                .dirty
+                .as_ref()
+                .map(|range| BladeStateUpdate::Partial {
+                    offset: range.start,
+                    states: states.states()[range.clone()].to_vec(),
+                })
+        };
+        values.push((entity, ExtractedBladeStates { update }));
+    }
+    commands.insert_or_spawn_batch(values);
+}
+/// Extracts the [`GrassDisplacer`]s closest to the camera.
+///
+/// Displacers without visibility components are always used
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_displacers(
+    mut commands: Commands,
+    displacers: Extract<
+        Query<(
+            &GrassDisplacer,
+            &GlobalTransform,
+            Option<&InheritedVisibility>,
+        )>,
+    >,
+    cameras: Extract<Query<(&Camera, &GlobalTransform)>>,
+) {
+    let values = displacers
+        .iter()
+        .filter(|(_, _, visibility)| visibility.is_none_or(|visibility| visibility.get()))
+        .map(|(displacer, transform, _)| {
+            let position = transform.translation();
+            (position, ShaderDisplacer::new(displacer, position))
+        });
+    commands.insert_resource(ExtractedDisplacers(closest_to_camera(
+        values,
+        &cameras,
+        MAX_DISPLACERS,
+    )));
+}
+/// Extracts the [`GrassTrail`].
+///
+/// Unlike the extracted resources, the trail is removed from the render world once it is removed from the main world
+pub(crate) fn extract_grass_trail(mut commands: Commands, trail: Extract<Option<Res<GrassTrail>>>) {
+    match trail.as_ref() {
+        Some(trail) => commands.insert_resource(trail.as_ref().clone()),
+        None => commands.remove_resource::<GrassTrail>(),
+    }
+}
+/// Extracts the [`WindZone`]s closest to the camera
+pub(crate) fn extract_wind_zones(
+    mut commands: Commands,
+    zones: Extract<Query<(&WindZone, &GlobalTransform)>>,
+    cameras: Extract<Query<(&Camera, &GlobalTransform)>>,
+) {
+    let values = zones.iter().map(|(zone, transform)| {
+        let position = transform.translation();
+        (position, ShaderWindZone::new(zone, position))
+    });
+    commands.insert_resource(ExtractedWindZones(closest_to_camera(
+        values,
+        &cameras,
+        MAX_WIND_ZONES,
+    )));
+}
+/// Returns at most `max` values, preferring the ones closest to the first active camera
+fn closest_to_camera<T>(
+    values: impl Iterator<Item = (Vec3, T)>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+    max: usize,
+) -> Vec<T> {
+    let camera_position = cameras
+        .iter()
+        .find(|(camera, _)| camera.is_active)
+        .map_or(Vec3::ZERO, |(_, transform)| transform.translation());
+    let mut values: Vec<(f32, T)> = values
+        .map(|(position, value)| (position.distance_squared(camera_position), value))
+        .collect();
+    if values.len() > max {
+        values.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        values.truncate(max);
+    }
+    values.into_iter().map(|(_, value)| value).collect()
 }
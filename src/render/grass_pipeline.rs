@@ -1,3 +1,5 @@
+use std::{mem, num::NonZeroU64};
+
 use bevy::{
     pbr::{MeshPipeline, MeshPipelineKey},
     prelude::*,
@@ -5,7 +7,7 @@ use bevy::{
         mesh::MeshVertexBufferLayout,
         render_resource::{
             BindGroupLayout, BindGroupLayoutEntry, BindingType, BufferBindingType,
-            RenderPipelineDescriptor, ShaderStages, SpecializedMeshPipeline,
+            RenderPipelineDescriptor, SamplerBindingType, ShaderStages, SpecializedMeshPipeline,
             SpecializedMeshPipelineError, TextureSampleType, TextureViewDimension, VertexAttribute,
             VertexBufferLayout, VertexFormat, VertexStepMode,
         },
@@ -13,15 +15,18 @@ use bevy::{
     },
 };
 
-use crate::warblers_plugin::GRASS_SHADER_HANDLE;
-#[derive(Resource)]
+use super::prepare::ShaderInstanceIndex;
+use crate::{
+    cutting::BladeState,
+    shader_hooks::{GrassShaderHookFlags, GrassShaderStage},
+    warblers_plugin::GRASS_SHADER_HANDLE,
+};
+#[derive(Resource, Clone)]
 pub struct GrassPipeline {
     shader: Handle<Shader>,
     mesh_pipeline: MeshPipeline,
     pub region_layout: BindGroupLayout,
     pub y_map_layout: BindGroupLayout,
-    pub normal_map_layout: BindGroupLayout,
-    pub density_map_layout: BindGroupLayout,
     pub heights_texture_layout: BindGroupLayout,
     pub uniform_height_layout: BindGroupLayout,
     pub color_layout: BindGroupLayout,
@@ -56,10 +61,43 @@ impl FromWorld for GrassPipeline {
                     },
                     count: None,
                 },
+                // displacers
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // displacement trail texture
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // wind zones
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         );
         let y_map_layout = render_device.create_bind_group_layout(
-            Some("warbler_grass y map layout"),
+            Some("warbler_grass y map, normal map and color map layout"),
             &[
                 // y_texture
                 BindGroupLayoutEntry {
@@ -83,14 +121,9 @@ impl FromWorld for GrassPipeline {
                     },
                     count: None,
                 },
-            ],
-        );
-        let normal_map_layout = render_device.create_bind_group_layout(
-            Some("warbler_grass normal map layout"),
-            &[
                 // normal_texture_view
                 BindGroupLayoutEntry {
-                    binding: 0,
+                    binding: 2,
                     visibility: ShaderStages::VERTEX,
                     ty: BindingType::Texture {
                         sample_type: TextureSampleType::Float { filterable: false },
@@ -99,14 +132,9 @@ impl FromWorld for GrassPipeline {
                     },
                     count: None,
                 },
-            ],
-        );
-        let density_map_layout = render_device.create_bind_group_layout(
-            Some("warbler_grass density map layout"),
-            &[
-                // density map
+                // color map
                 BindGroupLayoutEntry {
-                    binding: 0,
+                    binding: 3,
                     visibility: ShaderStages::VERTEX,
                     ty: BindingType::Texture {
                         sample_type: TextureSampleType::Float { filterable: false },
@@ -117,7 +145,6 @@ impl FromWorld for GrassPipeline {
                 },
             ],
         );
-
         let heights_texture_layout = render_device.create_bind_group_layout(
             Some("warbler_grass height texture layout"),
             &[BindGroupLayoutEntry {
@@ -146,16 +173,37 @@ impl FromWorld for GrassPipeline {
         );
         let color_layout = render_device.create_bind_group_layout(
             Some("warbler_grass color layout"),
-            &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            &[
+                // color
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                // albedo texture
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // albedo sampler
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
         );
 
         let instance_index_bind_group_layout = render_device.create_bind_group_layout(
@@ -165,8 +213,8 @@ impl FromWorld for GrassPipeline {
                 visibility: ShaderStages::VERTEX,
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                    has_dynamic_offset: true,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<ShaderInstanceIndex>() as u64),
                 },
                 count: None,
             }],
@@ -179,9 +227,7 @@ impl FromWorld for GrassPipeline {
             region_layout,
             uniform_height_layout,
             heights_texture_layout,
-            density_map_layout,
             y_map_layout,
-            normal_map_layout,
             color_layout,
             instance_index_bind_group_layout,
         }
@@ -195,6 +241,14 @@ impl SpecializedMeshPipeline for GrassPipeline {
         key: Self::Key,
         layout: &MeshVertexBufferLayout,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        // the mesh pipeline accepts meshes without positions, but the grass shader needs them
+        layout.get_layout(&[Mesh::ATTRIBUTE_POSITION.at_shader_location(0)])?;
+        if key.albedo {
+            // without uvs the whole texture would be sampled at a single texel
+            layout.get_layout(&[Mesh::ATTRIBUTE_UV_0.at_shader_location(2)])?;
+        }
+        // the mesh pipeline adds the normals, uvs and colors of the mesh layout
+        // together with the VERTEX_NORMALS, VERTEX_UVS and VERTEX_COLORS shader defs
         let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key, layout)?;
         let vertex = &mut descriptor.vertex;
         descriptor.label = Some("Grass Render Pipeline".into());
@@ -212,6 +266,26 @@ impl SpecializedMeshPipeline for GrassPipeline {
         if !key.uniform_height {
             vertex.shader_defs.push("HEIGHT_TEXTURE".into());
         }
+        if key.albedo {
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.push("ALBEDO_TEXTURE".into());
+            if key.mesh_key.msaa_samples() > 1 {
+                fragment.shader_defs.push("ALPHA_TO_COVERAGE".into());
+                descriptor.multisample.alpha_to_coverage_enabled = true;
+            }
+        }
+        if key.billboard {
+            vertex.shader_defs.push("BILLBOARD".into());
+        }
+        if key.lighting {
+            let fragment = descriptor.fragment.as_mut().unwrap();
+            fragment.shader_defs.push("DIFFUSE_LIGHTING".into());
+        }
+        for stage in GrassShaderStage::ALL {
+            if key.hooks.contains(stage.flag()) {
+                vertex.shader_defs.push(stage.shader_def().into());
+            }
+        }
         // set buffers
         vertex.buffers.push(VertexBufferLayout {
             array_stride: std::mem::size_of::<Vec2>() as u64,
@@ -219,9 +293,21 @@ impl SpecializedMeshPipeline for GrassPipeline {
             attributes: vec![VertexAttribute {
                 format: VertexFormat::Float32x2,
                 offset: 0,
-                shader_location: 3, // shader locations 0-2 may be taken up by Position, Normal and UV attributes
+                shader_location: 9, // locations up to 7 may be used by the mesh, 8 by the blade states
             }],
         });
+        if key.blade_states {
+            vertex.shader_defs.push("BLADE_STATES".into());
+            vertex.buffers.push(VertexBufferLayout {
+                array_stride: std::mem::size_of::<BladeState>() as u64,
+                step_mode: VertexStepMode::Instance,
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 8, // locations up to 7 may be used by the mesh
+                }],
+            });
+        }
 
         // set layouts
         // layouts that are added to the descriptor.
@@ -235,7 +321,6 @@ impl SpecializedMeshPipeline for GrassPipeline {
             &self.color_layout,
             &self.y_map_layout,
             &self.region_layout,
-            &self.normal_map_layout,
             &self.instance_index_bind_group_layout,
         ];
         for layout in layouts {
@@ -249,6 +334,15 @@ impl SpecializedMeshPipeline for GrassPipeline {
 pub struct GrassRenderKey {
     pub mesh_key: MeshPipelineKey,
     pub uniform_height: bool,
+    pub blade_states: bool,
+    /// Whether the chunk has a [`GrassAlbedo`](crate::prelude::GrassAlbedo)
+    pub albedo: bool,
+    /// Whether the chunk has a [`GrassBillboard`](crate::prelude::GrassBillboard)
+    pub billboard: bool,
+    /// Whether the chunk has a [`GrassLighting`](crate::prelude::GrassLighting)
+    pub lighting: bool,
+    /// The stages of the grass shader that are replaced by a hook
+    pub hooks: GrassShaderHookFlags,
 }
 
 impl From<MeshPipelineKey> for GrassRenderKey {
@@ -256,6 +350,11 @@ impl From<MeshPipelineKey> for GrassRenderKey {
         Self {
             mesh_key,
             uniform_height: false,
+            blade_states: false,
+            albedo: false,
+            billboard: false,
+            lighting: false,
+            hooks: GrassShaderHookFlags::empty(),
         }
     }
 }
@@ -3,11 +3,16 @@ use std::mem;
 use std::num::NonZeroU64;
 use std::ops::Mul;
 
-use super::cache::UniformBuffer;
+use super::cache::{GpuBladeState, GpuBladeStates, InstanceIndexBuffer, UniformBuffer};
+use super::extract::{BladeStateUpdate, ExtractedBladeStates};
 use super::grass_pipeline::GrassPipeline;
 use crate::bundle::WarblerHeight;
-use crate::map::{NormalMap, YMap};
-use crate::prelude::GrassColor;
+use crate::cutting::BladeState;
+use crate::displacement::{ExtractedDisplacers, GrassTrail, ShaderDisplacers};
+use crate::map::{ColorMap, NormalMap, YMap};
+use crate::prelude::{GrassAlbedo, GrassColor};
+use crate::warblers_plugin::DEFAULT_NORMAL_MAP_HANDLE;
+use crate::wind::{ExtractedWindZones, GrassWindOverride, ShaderWindZones, WindPhase};
 use crate::{GrassConfiguration, GrassNoiseTexture};
 use bevy::core_pipeline::core_3d::Opaque3d;
 use bevy::prelude::*;
@@ -18,7 +23,7 @@ use bevy::render::render_resource::{
     BindGroup, BindGroupEntries, BindingResource, BufferBinding, BufferInitDescriptor,
     BufferUsages, TextureViewId,
 };
-use bevy::render::renderer::RenderDevice;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
 use bevy::render::texture::FallbackImage;
 use bytemuck::{Pod, Zeroable};
 #[derive(Component)]
@@ -34,44 +39,126 @@ impl<T> BindGroupBuffer<T> {
         }
     }
 }
-#[derive(Component)]
-pub(crate) struct IndexBindgroup {
-    pub bind_group: BindGroup,
-}
+/// Writes the mesh uniform index of the chunks queued in each view into a single buffer.
+///
+/// The index of a chunk might differ between views, so each view gets its own entry
 pub(crate) fn prepare_instance_index(
-    query: Query<Entity, With<GrassColor>>,
-    mut commands: Commands,
-    phases: Query<&RenderPhase<Opaque3d>>,
+    chunks: Query<Option<&GrassWindOverride>, With<GrassColor>>,
+    views: Query<(Entity, &RenderPhase<Opaque3d>)>,
     pipeline: Res<GrassPipeline>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut instance_indices: ResMut<InstanceIndexBuffer>,
 ) {
-    for entity in &query {
-        let Some(item) = phases
-            .iter()
-            .flat_map(|phase| &phase.items)
-            .find(|item| item.entity == entity)
-        else {
-            continue;
-        };
-        let index_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("instance index buffer"),
-            contents: bytemuck::cast_slice(&[item.batch_range.start, 0, 0, 0]),
-            usage: BufferUsages::VERTEX | BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-        });
-        let layout = &pipeline.instance_index_bind_group_layout;
-        let bind_group = render_device.create_bind_group(
-            "instance index bindgroup",
-            layout,
-            &BindGroupEntries::single(BindingResource::Buffer(BufferBinding {
-                buffer: &index_buffer,
-                offset: 0,
-                size: None,
-            })),
-        );
-
-        commands
-            .entity(entity)
-            .insert(IndexBindgroup { bind_group });
+    let instance_indices = instance_indices.as_mut();
+    // every entry has to start at a valid dynamic offset
+    let alignment = render_device.limits().min_uniform_buffer_offset_alignment as usize;
+    let stride = mem::size_of::<ShaderInstanceIndex>().next_multiple_of(alignment);
+    instance_indices.data.clear();
+    instance_indices.offsets.clear();
+    for (view, phase) in &views {
+        for item in &phase.items {
+            let Ok(wind_override) = chunks.get(item.entity) else {
+                continue;
+            };
+            let offset = instance_indices.data.len();
+            let index = ShaderInstanceIndex::new(item.batch_range.start, wind_override);
+            instance_indices
+                .data
+                .extend_from_slice(bytemuck::bytes_of(&index));
+            instance_indices.data.resize(offset + stride, 0);
+            instance_indices
+                .offsets
+                .insert((view, item.entity), offset as u32);
+        }
+    }
+    if instance_indices.data.is_empty() {
+        return;
+    }
+    // the buffer is only recreated if it is too small
+    let size = instance_indices.data.len() as u64;
+    if let Some(buffer) = instance_indices
+        .buffer
+        .as_ref()
+        .filter(|buffer| buffer.size() >= size)
+    {
+        render_queue.write_buffer(buffer, 0, &instance_indices.data);
+        return;
+    }
+    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("instance index buffer"),
+        contents: &instance_indices.data,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    instance_indices.bind_group = Some(render_device.create_bind_group(
+        "instance index bindgroup",
+        &pipeline.instance_index_bind_group_layout,
+        &BindGroupEntries::single(BindingResource::Buffer(BufferBinding {
+            buffer: &buffer,
+            offset: 0,
+            size: NonZeroU64::new(mem::size_of::<ShaderInstanceIndex>() as u64),
+        })),
+    ));
+    instance_indices.buffer = Some(buffer);
+}
+/// Uploads the changed blade states of the chunks to the gpu
+pub(crate) fn prepare_blade_states(
+    chunks: Query<(Entity, &ExtractedBladeStates)>,
+    mut gpu_states: ResMut<GpuBladeStates>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    // forget the buffers of chunks that don't exist anymore
+    gpu_states.0.retain(|entity, _| chunks.contains(*entity));
+    for (entity, extracted) in &chunks {
+        match &extracted.update {
+            Some(BladeStateUpdate::Full(states)) => {
+                let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("grass blade state buffer"),
+                    contents: bytemuck::cast_slice(states.as_slice()),
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                });
+                gpu_states.0.insert(
+                    entity,
+                    GpuBladeState {
+                        buffer,
+                        len: states.len(),
+                    },
+                );
+            }
+            Some(BladeStateUpdate::Partial { offset, states }) => {
+                let Some(gpu_state) = gpu_states.0.get(&entity) else {
+                    continue;
+                };
+                if offset + states.len() > gpu_state.len {
+                    continue;
+                }
+                render_queue.write_buffer(
+                    &gpu_state.buffer,
+                    (offset * mem::size_of::<BladeState>()) as u64,
+                    bytemuck::cast_slice(states.as_slice()),
+                );
+            }
+            None => {}
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ShaderInstanceIndex {
+    /// The index of the chunk in the mesh uniform buffer
+    index: u32,
+    /// 1 if the chunk has a [`GrassWindOverride`]
+    has_wind_override: u32,
+    wind_override: Vec2,
+}
+impl ShaderInstanceIndex {
+    fn new(index: u32, wind_override: Option<&GrassWindOverride>) -> Self {
+        Self {
+            index,
+            has_wind_override: wind_override.is_some() as u32,
+            wind_override: wind_override.map_or(Vec2::ZERO, |wind| wind.0),
+        }
     }
 }
 #[derive(Component)]
@@ -135,24 +222,34 @@ pub(crate) fn prepare_grass_color(
     mut commands: Commands,
     pipeline: Res<GrassPipeline>,
     render_device: Res<RenderDevice>,
-    inserted_grass: Query<(Entity, &GrassColor)>,
+    fallback_img: Res<FallbackImage>,
+    images: Res<RenderAssets<Image>>,
+    inserted_grass: Query<(Entity, &GrassColor, Option<&GrassAlbedo>)>,
 ) {
-    for (entity, color) in inserted_grass.iter() {
+    for (entity, color, albedo) in inserted_grass.iter() {
         let layout = pipeline.color_layout.clone();
 
         let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
             label: "grass color buffer".into(),
-            contents: bytemuck::bytes_of(&ShaderColorUniform::from(color)),
+            contents: bytemuck::bytes_of(&ShaderColorUniform::new(color, albedo)),
             usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::UNIFORM,
         });
+        // chunks without a texture use a white image, which doesn't change the color
+        let albedo_image = albedo
+            .and_then(|albedo| images.get(&albedo.texture))
+            .unwrap_or(&fallback_img.d2);
         let bind_group = render_device.create_bind_group(
             "grass color bind group",
             &layout,
-            &BindGroupEntries::single(BindingResource::Buffer(BufferBinding {
-                buffer: &buffer,
-                offset: 0,
-                size: NonZeroU64::new(mem::size_of::<ShaderColorUniform>() as u64),
-            })),
+            &BindGroupEntries::sequential((
+                BindingResource::Buffer(BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(mem::size_of::<ShaderColorUniform>() as u64),
+                }),
+                BindingResource::TextureView(&albedo_image.texture_view),
+                BindingResource::Sampler(&albedo_image.sampler),
+            )),
         );
         commands
             .entity(entity)
@@ -160,22 +257,35 @@ pub(crate) fn prepare_grass_color(
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub(crate) fn prepare_y_map_buffer(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     pipeline: Res<GrassPipeline>,
     fallback_img: Res<FallbackImage>,
     images: Res<RenderAssets<Image>>,
-    inserted_grass: Query<(Entity, &YMap, &Aabb)>,
+    inserted_grass: Query<(Entity, &YMap, &Aabb, Option<&NormalMap>, Option<&ColorMap>)>,
 ) {
     let layout = pipeline.y_map_layout.clone();
 
-    for (entity, y_map, aabb) in inserted_grass.iter() {
+    for (entity, y_map, aabb, normal_map, color_map) in inserted_grass.iter() {
         let y_map_texture = if let Some(tex) = images.get(&y_map.y_map) {
             &tex.texture_view
         } else {
             &fallback_img.d2.texture_view
         };
+        // chunks without a normal map point straight up
+        let normal_map_id =
+            normal_map.map_or(DEFAULT_NORMAL_MAP_HANDLE.id(), |map| map.normal_map.id());
+        let normal_map_texture = if let Some(tex) = images.get(normal_map_id) {
+            &tex.texture_view
+        } else {
+            &fallback_img.d2.texture_view
+        };
+        // the white fallback image leaves the colors untouched
+        let color_map_texture = color_map
+            .and_then(|map| images.get(&map.color_map))
+            .map_or(&fallback_img.d2.texture_view, |tex| &tex.texture_view);
 
         let shader_aabb = ShaderAabb::from(Vec3::from(aabb.half_extents.mul(2.)));
         let aabb_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
@@ -185,7 +295,7 @@ pub(crate) fn prepare_y_map_buffer(
         });
 
         let bind_group = render_device.create_bind_group(
-            "grass y-map bind group",
+            "grass y-map, normal map and color map bind group",
             &layout,
             &BindGroupEntries::sequential((
                 BindingResource::TextureView(y_map_texture),
@@ -194,6 +304,8 @@ pub(crate) fn prepare_y_map_buffer(
                     offset: 0,
                     size: None,
                 }),
+                BindingResource::TextureView(normal_map_texture),
+                BindingResource::TextureView(color_map_texture),
             )),
         );
         commands
@@ -201,33 +313,6 @@ pub(crate) fn prepare_y_map_buffer(
             .insert(BindGroupBuffer::<YMap>::new(bind_group));
     }
 }
-pub(crate) fn prepare_normal_map_buffer(
-    mut commands: Commands,
-    render_device: Res<RenderDevice>,
-    pipeline: Res<GrassPipeline>,
-    fallback_img: Res<FallbackImage>,
-    images: Res<RenderAssets<Image>>,
-    inserted_grass: Query<(Entity, &NormalMap)>,
-) {
-    let layout = pipeline.normal_map_layout.clone();
-
-    for (entity, normal_map) in inserted_grass.iter() {
-        let normal_map_texture = if let Some(tex) = images.get(&normal_map.normal_map) {
-            &tex.texture_view
-        } else {
-            &fallback_img.d2.texture_view
-        };
-
-        let bind_group = render_device.create_bind_group(
-            "grass normal-map bind group",
-            &layout,
-            &BindGroupEntries::single(BindingResource::TextureView(normal_map_texture)),
-        );
-        commands
-            .entity(entity)
-            .insert(BindGroupBuffer::<NormalMap>::new(bind_group));
-    }
-}
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn prepare_uniform_buffers(
     pipeline: Res<GrassPipeline>,
@@ -237,7 +322,10 @@ pub(crate) fn prepare_uniform_buffers(
     render_device: Res<RenderDevice>,
     mut uniform_buffer: ResMut<UniformBuffer>,
     images: Res<RenderAssets<Image>>,
-    time: Res<Time>,
+    wind_phase: Res<WindPhase>,
+    displacers: Res<ExtractedDisplacers>,
+    trail: Option<Res<GrassTrail>>,
+    wind_zones: Res<ExtractedWindZones>,
     mut last_texture_id: Local<Option<TextureViewId>>,
 ) {
     let texture = &images
@@ -246,14 +334,30 @@ pub(crate) fn prepare_uniform_buffers(
         .texture_view;
     *last_texture_id = Some(texture.id());
 
-    let shader_config =
-        ShaderRegionConfiguration::new(region_config.as_ref(), time.elapsed_seconds_wrapped());
+    let shader_config = ShaderRegionConfiguration::new(region_config.as_ref(), wind_phase.as_ref());
     let config_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: Some("region config buffer"),
         contents: bytemuck::bytes_of(&shader_config),
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     });
 
+    let shader_displacers =
+        ShaderDisplacers::new(&displacers.0, trail.as_ref().map(|trail| trail.area));
+    let displacers_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("grass displacers buffer"),
+        contents: bytemuck::bytes_of(&shader_displacers),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let wind_zones_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("grass wind zones buffer"),
+        contents: bytemuck::bytes_of(&ShaderWindZones::new(&wind_zones.0)),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let trail_texture = &trail
+        .and_then(|trail| images.get(&trail.texture))
+        .unwrap_or(&fallback_img.d2)
+        .texture_view;
+
     let layout = pipeline.region_layout.clone();
     let bind_group = render_device.create_bind_group(
         "grass uniform bind group ",
@@ -265,6 +369,17 @@ pub(crate) fn prepare_uniform_buffers(
                 size: None,
             }),
             BindingResource::TextureView(texture),
+            BindingResource::Buffer(BufferBinding {
+                buffer: &displacers_buffer,
+                offset: 0,
+                size: None,
+            }),
+            BindingResource::TextureView(trail_texture),
+            BindingResource::Buffer(BufferBinding {
+                buffer: &wind_zones_buffer,
+                offset: 0,
+                size: None,
+            }),
         )),
     );
     uniform_buffer.set(bind_group);
@@ -273,21 +388,40 @@ pub(crate) fn prepare_uniform_buffers(
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 struct ShaderRegionConfiguration {
-    /// The time since startup in seconds.
-    /// Wraps to 0 after 1 hour
-    time: f32,
-    /// Wasm requires shader uniforms to be aligned to 16 bytes
-    _wasm_padding: f32,
+    /// The offset of the wind noise texture.
+    /// Wraps at the size of the texture
+    noise_offset: Vec2,
+    /// The phase of the gusts in the range [0, 1)
+    gust_phase: f32,
+    /// Scales the wind
+    wind_strength: f32,
     /// Direction of the wind
     wind: Vec2,
+    /// Scroll speed of the wind noise
+    wind_speed: f32,
+    /// Zoom of the wind noise
+    wind_scale: f32,
+    gust_amplitude: f32,
+    phase_variation: f32,
+    gust_length: f32,
+    /// Wasm requires shader uniforms to be aligned to 16 bytes
+    _wasm_padding: f32,
 }
 
 impl ShaderRegionConfiguration {
-    pub fn new(config: &GrassConfiguration, time: f32) -> ShaderRegionConfiguration {
+    pub fn new(config: &GrassConfiguration, phase: &WindPhase) -> ShaderRegionConfiguration {
         Self {
+            noise_offset: phase.noise_offset,
+            gust_phase: phase.gust_phase,
+            wind_strength: config.wind_strength,
             wind: config.wind,
+            wind_speed: config.wind_speed,
+            wind_scale: config.wind_scale,
+            gust_amplitude: config.gust_amplitude,
+            phase_variation: config.phase_variation,
+            // avoids a division by zero in the shader
+            gust_length: config.gust_length.max(f32::EPSILON),
             _wasm_padding: 0.,
-            time,
         }
     }
 }
@@ -313,12 +447,18 @@ impl From<Vec3> for ShaderAabb {
 struct ShaderColorUniform {
     main_color: Vec4,
     bottom_color: Vec4,
+    /// The alpha cutoff of the [`GrassAlbedo`]
+    alpha_cutoff: f32,
+    /// Wasm requires shader uniforms to be aligned to 16 bytes
+    _wasm_padding: [f32; 3],
 }
-impl From<&GrassColor> for ShaderColorUniform {
-    fn from(config: &GrassColor) -> Self {
+impl ShaderColorUniform {
+    fn new(config: &GrassColor, albedo: Option<&GrassAlbedo>) -> Self {
         Self {
             main_color: config.main_color.rgba_to_vec4(),
             bottom_color: config.bottom_color.rgba_to_vec4(),
+            alpha_cutoff: albedo.map_or(0., |albedo| albedo.alpha_cutoff),
+            _wasm_padding: [0.; 3],
         }
     }
 }
@@ -1,18 +1,37 @@
+use std::hash::Hash;
+
 use bevy::core_pipeline::core_3d::Opaque3d;
 use bevy::core_pipeline::prepass::{
     DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass,
 };
 use bevy::pbr::{MeshPipelineKey, RenderMeshInstances};
 use bevy::prelude::*;
+use bevy::render::mesh::GpuMesh;
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_phase::{DrawFunctions, RenderPhase};
 use bevy::render::render_resource::{PipelineCache, SpecializedMeshPipelines};
-use bevy::render::view::ExtractedView;
+use bevy::render::view::{ExtractedView, VisibleEntities};
 
-use crate::prelude::WarblerHeight;
+use crate::material::{
+    GrassMaterial, GrassMaterialFlag, GrassMaterialKey, GrassMaterialPipeline, RenderGrassMaterials,
+};
+use crate::prelude::{GrassAlbedo, GrassBillboard, GrassLighting, WarblerHeight};
+use crate::render_error::GrassRenderErrors;
+use crate::shader_hooks::GrassShaderHookFlags;
 
+use super::extract::ExtractedBladeStates;
 use super::grass_pipeline::{GrassPipeline, GrassRenderKey};
-use super::GrassDrawCall;
+use super::{DrawGrassMaterial, GrassDrawCall};
+
+type ViewQuery<'a> = (
+    &'a ExtractedView,
+    &'a VisibleEntities,
+    &'a mut RenderPhase<Opaque3d>,
+    Has<DepthPrepass>,
+    Has<NormalPrepass>,
+    Has<MotionVectorPrepass>,
+    Has<DeferredPrepass>,
+);
 
 #[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub(crate) fn queue_grass_buffers(
@@ -23,36 +42,57 @@ pub(crate) fn queue_grass_buffers(
     pipeline_cache: Res<PipelineCache>,
     render_mesh_instances: Res<RenderMeshInstances>,
     meshes: Res<RenderAssets<Mesh>>,
-    material_meshes: Query<(Entity, &WarblerHeight)>,
-    mut views: Query<(
-        &ExtractedView,
-        &mut RenderPhase<Opaque3d>,
-        Has<DepthPrepass>,
-        Has<NormalPrepass>,
-        Has<MotionVectorPrepass>,
-        Has<DeferredPrepass>,
-    )>,
+    material_meshes: Query<
+        (
+            Entity,
+            &WarblerHeight,
+            Has<ExtractedBladeStates>,
+            Has<GrassAlbedo>,
+            Has<GrassBillboard>,
+            Has<GrassLighting>,
+        ),
+        Without<GrassMaterialFlag>,
+    >,
+    hooks: Res<GrassShaderHookFlags>,
+    mut errors: ResMut<GrassRenderErrors>,
+    mut views: Query<ViewQuery>,
 ) {
     let draw_custom = opaque_3d_draw_functions.read().id::<GrassDrawCall>();
-    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+    // errors of despawned chunks and unloaded meshes are forgotten
+    errors.prune(
+        |entity| {
+            render_mesh_instances
+                .get(&entity)
+                .map(|instance| instance.mesh_asset_id)
+        },
+        |mesh| meshes.get(mesh).is_some(),
+    );
 
-    for (view, mut opaque_phase, depth_prepass, normal_prepass, motion_prepass, deferred_prepass) in
-        &mut views
+    for (
+        view,
+        visible_entities,
+        mut opaque_phase,
+        depth_prepass,
+        normal_prepass,
+        motion_prepass,
+        deferred_prepass,
+    ) in &mut views
     {
-        let mut view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
-        if deferred_prepass {
-            view_key |= MeshPipelineKey::DEFERRED_PREPASS;
-        }
-        if depth_prepass {
-            view_key |= MeshPipelineKey::DEPTH_PREPASS;
-        }
-        if normal_prepass {
-            view_key |= MeshPipelineKey::NORMAL_PREPASS;
-        }
-        if motion_prepass {
-            view_key |= MeshPipelineKey::MOTION_VECTOR_PREPASS;
-        }
-        for (entity, height) in material_meshes.iter() {
+        let view_key = view_key(
+            &msaa,
+            view,
+            depth_prepass,
+            normal_prepass,
+            motion_prepass,
+            deferred_prepass,
+        );
+        // only chunks visible in this view are queued, which respects their `RenderLayers`
+        for visible_entity in &visible_entities.entities {
+            let Ok((entity, height, blade_states, albedo, billboard, lighting)) =
+                material_meshes.get(*visible_entity)
+            else {
+                continue;
+            };
             let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
                 continue;
             };
@@ -60,16 +100,31 @@ pub(crate) fn queue_grass_buffers(
             let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
                 continue;
             };
-            let mesh_key =
-                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
-            let mut grass_key = GrassRenderKey::from(mesh_key);
-            grass_key.uniform_height = match height {
-                WarblerHeight::Uniform(_) => true,
-                WarblerHeight::Texture(_) => false,
+            let grass_key = grass_key(
+                view_key,
+                mesh,
+                height,
+                blade_states,
+                albedo,
+                billboard,
+                lighting,
+                *hooks,
+            );
+            let pipeline = match pipelines.specialize(
+                &pipeline_cache,
+                &grass_pipeline,
+                grass_key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => {
+                    errors.resolve(entity, mesh_instance.mesh_asset_id);
+                    pipeline
+                }
+                Err(error) => {
+                    errors.report(entity, mesh_instance.mesh_asset_id, error);
+                    continue;
+                }
             };
-            let pipeline = pipelines
-                .specialize(&pipeline_cache, &grass_pipeline, grass_key, &mesh.layout)
-                .unwrap();
             opaque_phase.add(Opaque3d {
                 entity,
                 pipeline,
@@ -81,3 +136,152 @@ pub(crate) fn queue_grass_buffers(
         }
     }
 }
+/// Queues the chunks drawn with the [`GrassMaterial`] `M`
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn queue_grass_material_buffers<M: GrassMaterial>(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    material_pipeline: Res<GrassMaterialPipeline<M>>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<GrassMaterialPipeline<M>>>,
+    pipeline_cache: Res<PipelineCache>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderGrassMaterials<M>>,
+    material_meshes: Query<(
+        Entity,
+        &WarblerHeight,
+        Has<ExtractedBladeStates>,
+        Has<GrassAlbedo>,
+        Has<GrassBillboard>,
+        Has<GrassLighting>,
+        &Handle<M>,
+    )>,
+    hooks: Res<GrassShaderHookFlags>,
+    mut errors: ResMut<GrassRenderErrors>,
+    mut views: Query<ViewQuery>,
+) where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    let draw_material = opaque_3d_draw_functions.read().id::<DrawGrassMaterial<M>>();
+
+    for (
+        view,
+        visible_entities,
+        mut opaque_phase,
+        depth_prepass,
+        normal_prepass,
+        motion_prepass,
+        deferred_prepass,
+    ) in &mut views
+    {
+        let view_key = view_key(
+            &msaa,
+            view,
+            depth_prepass,
+            normal_prepass,
+            motion_prepass,
+            deferred_prepass,
+        );
+        for visible_entity in &visible_entities.entities {
+            let Ok((entity, height, blade_states, albedo, billboard, lighting, material)) =
+                material_meshes.get(*visible_entity)
+            else {
+                continue;
+            };
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let Some(prepared_material) = render_materials.get(&material.id()) else {
+                continue;
+            };
+            let key = GrassMaterialKey {
+                grass_key: grass_key(
+                    view_key,
+                    mesh,
+                    height,
+                    blade_states,
+                    albedo,
+                    billboard,
+                    lighting,
+                    *hooks,
+                ),
+                bind_group_data: prepared_material.key.clone(),
+            };
+            let pipeline = match pipelines.specialize(
+                &pipeline_cache,
+                &material_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => {
+                    errors.resolve(entity, mesh_instance.mesh_asset_id);
+                    pipeline
+                }
+                Err(error) => {
+                    errors.report(entity, mesh_instance.mesh_asset_id, error);
+                    continue;
+                }
+            };
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_material,
+                batch_range: 0..1,
+                dynamic_offset: None,
+                asset_id: mesh_instance.mesh_asset_id,
+            });
+        }
+    }
+}
+/// The part of the pipeline key shared by all chunks drawn in a view
+fn view_key(
+    msaa: &Msaa,
+    view: &ExtractedView,
+    depth_prepass: bool,
+    normal_prepass: bool,
+    motion_prepass: bool,
+    deferred_prepass: bool,
+) -> MeshPipelineKey {
+    let mut view_key =
+        MeshPipelineKey::from_msaa_samples(msaa.samples()) | MeshPipelineKey::from_hdr(view.hdr);
+    if deferred_prepass {
+        view_key |= MeshPipelineKey::DEFERRED_PREPASS;
+    }
+    if depth_prepass {
+        view_key |= MeshPipelineKey::DEPTH_PREPASS;
+    }
+    if normal_prepass {
+        view_key |= MeshPipelineKey::NORMAL_PREPASS;
+    }
+    if motion_prepass {
+        view_key |= MeshPipelineKey::MOTION_VECTOR_PREPASS;
+    }
+    view_key
+}
+#[allow(clippy::too_many_arguments)]
+fn grass_key(
+    view_key: MeshPipelineKey,
+    mesh: &GpuMesh,
+    height: &WarblerHeight,
+    blade_states: bool,
+    albedo: bool,
+    billboard: bool,
+    lighting: bool,
+    hooks: GrassShaderHookFlags,
+) -> GrassRenderKey {
+    let mesh_key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+    let mut grass_key = GrassRenderKey::from(mesh_key);
+    grass_key.uniform_height = match height {
+        WarblerHeight::Uniform(_) => true,
+        WarblerHeight::Texture(_) => false,
+    };
+    grass_key.blade_states = blade_states;
+    grass_key.albedo = albedo;
+    grass_key.billboard = billboard;
+    grass_key.lighting = lighting;
+    grass_key.hooks = hooks;
+    grass_key
+}
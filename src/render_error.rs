@@ -0,0 +1,157 @@
+//! Contains the [`GrassRenderErrorEvent`], which reports grass chunks that can't be drawn.
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+use bevy::{prelude::*, render::render_resource::SpecializedMeshPipelineError, utils::HashSet};
+
+/// Sent if a chunk can't be drawn, because the grass pipeline can't be specialized for its mesh.
+///
+/// This usually means that the mesh has an unexpected vertex layout, like a missing position attribute.
+/// The chunk is skipped while drawing until its mesh is replaced.
+/// The event is sent once for each chunk and mesh
+#[derive(Event, Clone, Debug)]
+pub struct GrassRenderErrorEvent {
+    /// The entity of the chunk
+    pub entity: Entity,
+    /// The mesh of the chunk
+    pub mesh: AssetId<Mesh>,
+    /// A description of the error
+    pub error: String,
+}
+
+/// Collects the specialization errors in the render world and sends them to the main world
+#[derive(Resource)]
+pub(crate) struct GrassRenderErrors {
+    sender: Sender<GrassRenderErrorEvent>,
+    logged_meshes: HashSet<AssetId<Mesh>>,
+    reported: HashSet<(Entity, AssetId<Mesh>)>,
+}
+impl GrassRenderErrors {
+    /// Logs the error once per mesh and reports it once per chunk
+    pub fn report(
+        &mut self,
+        entity: Entity,
+        mesh: AssetId<Mesh>,
+        error: SpecializedMeshPipelineError,
+    ) {
+        if !self.reported.insert((entity, mesh)) {
+            return;
+        }
+        if self.logged_meshes.insert(mesh) {
+            error!("Failed to specialize the grass pipeline for mesh {mesh:?}: {error}");
+        }
+        // the main world might be gone already
+        let _ = self.sender.send(GrassRenderErrorEvent {
+            entity,
+            mesh,
+            error: error.to_string(),
+        });
+    }
+    /// Forgets the errors of a chunk after it was specialized successfully,
+    /// so an error is reported again if its mesh breaks later
+    pub fn resolve(&mut self, entity: Entity, mesh: AssetId<Mesh>) {
+        if self.reported.is_empty() {
+            return;
+        }
+        self.reported.retain(|(reported, _)| *reported != entity);
+        self.logged_meshes.remove(&mesh);
+    }
+    /// Removes the errors of chunks that no longer use the mesh they were reported for
+    /// and of meshes that are unloaded
+    pub fn prune(
+        &mut self,
+        current_mesh: impl Fn(Entity) -> Option<AssetId<Mesh>>,
+        mesh_exists: impl Fn(AssetId<Mesh>) -> bool,
+    ) {
+        if self.reported.is_empty() && self.logged_meshes.is_empty() {
+            return;
+        }
+        self.reported
+            .retain(|(entity, mesh)| current_mesh(*entity) == Some(*mesh));
+        self.logged_meshes.retain(|mesh| mesh_exists(*mesh));
+    }
+}
+/// Receives the [`GrassRenderErrorEvent`]s sent by the render world
+#[derive(Resource)]
+pub(crate) struct GrassRenderErrorReceiver(Mutex<Receiver<GrassRenderErrorEvent>>);
+
+/// Creates the connected resources of the render world and the main world
+pub(crate) fn render_error_channel() -> (GrassRenderErrors, GrassRenderErrorReceiver) {
+    let (sender, receiver) = channel();
+    (
+        GrassRenderErrors {
+            sender,
+            logged_meshes: HashSet::default(),
+            reported: HashSet::default(),
+        },
+        GrassRenderErrorReceiver(Mutex::new(receiver)),
+    )
+}
+/// Sends the errors of the render world as [`GrassRenderErrorEvent`]s
+pub(crate) fn forward_render_errors(
+    receiver: Res<GrassRenderErrorReceiver>,
+    mut events: EventWriter<GrassRenderErrorEvent>,
+) {
+    let Ok(receiver) = receiver.0.lock() else {
+        return;
+    };
+    events.send_batch(receiver.try_iter());
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        prelude::*,
+        render::{
+            mesh::PrimitiveTopology, render_asset::RenderAssetUsages,
+            render_resource::SpecializedMeshPipelineError,
+        },
+    };
+
+    use super::render_error_channel;
+
+    fn missing_position() -> SpecializedMeshPipelineError {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .get_mesh_vertex_buffer_layout()
+        .get_layout(&[Mesh::ATTRIBUTE_POSITION.at_shader_location(0)])
+        .unwrap_err()
+        .into()
+    }
+    #[test]
+    fn errors_are_reported_once_per_chunk() {
+        let (mut errors, receiver) = render_error_channel();
+        let mesh = AssetId::<Mesh>::default();
+        let (first, second) = (Entity::from_raw(1), Entity::from_raw(2));
+        errors.report(first, mesh, missing_position());
+        errors.report(first, mesh, missing_position());
+        errors.report(second, mesh, missing_position());
+
+        let receiver = receiver.0.lock().unwrap();
+        let entities: Vec<Entity> = receiver.try_iter().map(|event| event.entity).collect();
+        assert_eq!(entities, vec![first, second]);
+    }
+    #[test]
+    fn resolved_and_pruned_errors_are_reported_again() {
+        let (mut errors, receiver) = render_error_channel();
+        let mesh = AssetId::<Mesh>::default();
+        let (first, second) = (Entity::from_raw(1), Entity::from_raw(2));
+        errors.report(first, mesh, missing_position());
+        errors.report(second, mesh, missing_position());
+        // the mesh of the first chunk was fixed
+        errors.resolve(first, mesh);
+        errors.report(first, mesh, missing_position());
+        // the second chunk was despawned and spawned again with the same id
+        errors.prune(|entity| (entity == first).then_some(mesh), |_| true);
+        errors.report(second, mesh, missing_position());
+        errors.report(second, mesh, missing_position());
+
+        let receiver = receiver.0.lock().unwrap();
+        let entities: Vec<Entity> = receiver.try_iter().map(|event| event.entity).collect();
+        assert_eq!(entities, vec![first, second, first, second]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Allows replacing single stages of the built-in grass shader.
+//!
+//! The grass shader is split into importable WGSL modules (`warbler_grass::wind`, `warbler_grass::y_map`,
+//! `warbler_grass::normal`, `warbler_grass::height` and `warbler_grass::color`).
+//! Each of them is a stage that can be replaced by a small snippet registered in the [`GrassShaderHooks`].
+//! The snippet has to define the function of the stage with the documented signature
+//! and can import everything the built-in modules provide.
+//! ```
+//! # use warbler_grass::shader_hooks::{GrassShaderHooks, GrassShaderStage};
+//! let mut hooks = GrassShaderHooks::default();
+//! hooks.set(
+//!     GrassShaderStage::Color,
+//!     "fn color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32> {
+//!         return vec4<f32>(vertex_position.y, 0.5, 0.2, 1.0);
+//!     }",
+//! );
+//! ```
+use std::borrow::Cow;
+
+use bevy::{prelude::*, render::extract_resource::ExtractResource, utils::HashMap};
+use bitflags::bitflags;
+
+/// A stage of the built-in grass shader, which can be replaced using the [`GrassShaderHooks`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GrassShaderStage {
+    /// Bends the blades in the wind.
+    ///
+    /// `fn wind(world_root: vec3<f32>, field_position: vec2<f32>, local_wind: vec2<f32>) -> vec2<f32>`
+    ///
+    /// Returns the offset of the blade tip on the x,z plane.
+    /// `local_wind` is the wind of the chunk, which might be overridden by a [`GrassWindOverride`](crate::wind::GrassWindOverride)
+    Wind,
+    /// Places the blades on the terrain.
+    ///
+    /// `fn y_position(field_position: vec2<f32>) -> f32`
+    ///
+    /// Returns the y position of the blade root in the local space of the chunk
+    YMap,
+    /// Aligns the blades with the terrain.
+    ///
+    /// `fn alignment(field_position: vec2<f32>) -> mat3x3<f32>`
+    ///
+    /// Returns the rotation applied to the blade mesh
+    Normal,
+    /// Scales the blades.
+    ///
+    /// `fn height(field_position: vec2<f32>) -> f32`
+    ///
+    /// Returns the factor the blade mesh is scaled with on the y axis.
+    /// The shader def `HEIGHT_TEXTURE` is set if the chunk uses a height map
+    Height,
+    /// Colors the blades.
+    ///
+    /// `fn color(vertex_position: vec3<f32>, field_position: vec2<f32>) -> vec4<f32>`
+    ///
+    /// `vertex_position` is the position of the vertex in the blade mesh.
+    /// The [`ColorMap`](crate::map::ColorMap) of the chunk is bound as `warbler_grass::bindings::color_texture`
+    Color,
+}
+impl GrassShaderStage {
+    /// All stages that can be replaced
+    pub const ALL: [GrassShaderStage; 5] = [
+        GrassShaderStage::Wind,
+        GrassShaderStage::YMap,
+        GrassShaderStage::Normal,
+        GrassShaderStage::Height,
+        GrassShaderStage::Color,
+    ];
+    /// The import path of the hook module of the stage
+    pub fn import_path(self) -> &'static str {
+        match self {
+            GrassShaderStage::Wind => "warbler_grass::hooks::wind_hook",
+            GrassShaderStage::YMap => "warbler_grass::hooks::y_map_hook",
+            GrassShaderStage::Normal => "warbler_grass::hooks::normal_hook",
+            GrassShaderStage::Height => "warbler_grass::hooks::height_hook",
+            GrassShaderStage::Color => "warbler_grass::hooks::color_hook",
+        }
+    }
+    /// The shader def, which selects the hook over the built-in stage
+    pub(crate) fn shader_def(self) -> &'static str {
+        match self {
+            GrassShaderStage::Wind => "WIND_HOOK",
+            GrassShaderStage::YMap => "Y_MAP_HOOK",
+            GrassShaderStage::Normal => "NORMAL_HOOK",
+            GrassShaderStage::Height => "HEIGHT_HOOK",
+            GrassShaderStage::Color => "COLOR_HOOK",
+        }
+    }
+    pub(crate) fn flag(self) -> GrassShaderHookFlags {
+        match self {
+            GrassShaderStage::Wind => GrassShaderHookFlags::WIND,
+            GrassShaderStage::YMap => GrassShaderHookFlags::Y_MAP,
+            GrassShaderStage::Normal => GrassShaderHookFlags::NORMAL,
+            GrassShaderStage::Height => GrassShaderHookFlags::HEIGHT,
+            GrassShaderStage::Color => GrassShaderHookFlags::COLOR,
+        }
+    }
+    /// The handle of the hook module
+    fn handle(self) -> Handle<Shader> {
+        match self {
+            GrassShaderStage::Wind => Handle::weak_from_u128(8_181_616_933_485_341_065),
+            GrassShaderStage::YMap => Handle::weak_from_u128(7_517_963_798_635_514_932),
+            GrassShaderStage::Normal => Handle::weak_from_u128(8_731_802_435_330_353_620),
+            GrassShaderStage::Height => Handle::weak_from_u128(7_834_161_742_838_381_140),
+            GrassShaderStage::Color => Handle::weak_from_u128(8_346_428_511_231_277_415),
+        }
+    }
+    /// The hook used if none is registered, which calls the built-in stage
+    fn default_source(self) -> &'static str {
+        match self {
+            GrassShaderStage::Wind => include_str!("render/assets/hooks/wind_hook.wgsl"),
+            GrassShaderStage::YMap => include_str!("render/assets/hooks/y_map_hook.wgsl"),
+            GrassShaderStage::Normal => include_str!("render/assets/hooks/normal_hook.wgsl"),
+            GrassShaderStage::Height => include_str!("render/assets/hooks/height_hook.wgsl"),
+            GrassShaderStage::Color => include_str!("render/assets/hooks/color_hook.wgsl"),
+        }
+    }
+    fn shader(self, source: &str) -> Shader {
+        let import_path = self.import_path();
+        Shader::from_wgsl(
+            format!("#define_import_path {import_path}\n{source}"),
+            import_path,
+        )
+    }
+}
+/// A [resource](bevy::prelude::Resource) storing snippets which replace stages of the built-in grass shader.
+///
+/// The import path of the snippet is added automatically, so it must not contain a `#define_import_path`.
+/// Stages without a snippet use the built-in implementation.
+/// Changing the hooks recompiles the grass pipelines.
+///
+/// Inserted by the [`WarblersPlugin`](crate::warblers_plugin::WarblersPlugin)
+#[derive(Resource, Clone, Default)]
+pub struct GrassShaderHooks {
+    hooks: HashMap<GrassShaderStage, Cow<'static, str>>,
+}
+impl GrassShaderHooks {
+    /// Replaces the `stage` with the WGSL `source`
+    pub fn set(&mut self, stage: GrassShaderStage, source: impl Into<Cow<'static, str>>) {
+        self.hooks.insert(stage, source.into());
+    }
+    /// Removes the hook of the `stage`, so that the built-in implementation is used again
+    pub fn remove(&mut self, stage: GrassShaderStage) -> Option<Cow<'static, str>> {
+        self.hooks.remove(&stage)
+    }
+    /// Returns the source of the hook replacing the `stage`
+    pub fn get(&self, stage: GrassShaderStage) -> Option<&str> {
+        self.hooks.get(&stage).map(|source| source.as_ref())
+    }
+}
+
+bitflags! {
+    /// The stages replaced by a hook, as seen by the render world
+    #[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub(crate) struct GrassShaderHookFlags: u8 {
+        const WIND = 1 << 0;
+        const Y_MAP = 1 << 1;
+        const NORMAL = 1 << 2;
+        const HEIGHT = 1 << 3;
+        const COLOR = 1 << 4;
+    }
+}
+impl ExtractResource for GrassShaderHookFlags {
+    type Source = GrassShaderHooks;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source
+            .hooks
+            .keys()
+            .fold(GrassShaderHookFlags::empty(), |flags, stage| {
+                flags | stage.flag()
+            })
+    }
+}
+/// Adds the default hook modules, which call the built-in stages
+pub(crate) fn add_default_hooks(shaders: &mut Assets<Shader>) {
+    for stage in GrassShaderStage::ALL {
+        shaders.insert(stage.handle(), stage.shader(stage.default_source()));
+    }
+}
+/// Replaces the hook modules of all stages that changed since the last run
+pub(crate) fn update_shader_hooks(
+    hooks: Res<GrassShaderHooks>,
+    mut applied: Local<GrassShaderHooks>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    if !hooks.is_changed() {
+        return;
+    }
+    for stage in GrassShaderStage::ALL {
+        let source = hooks.get(stage);
+        if source == applied.get(stage) {
+            continue;
+        }
+        let source = source.unwrap_or(stage.default_source());
+        shaders.insert(stage.handle(), stage.shader(source));
+    }
+    *applied = hooks.clone();
+}
@@ -18,13 +18,34 @@ use bevy::{
         texture::{BevyDefault, FallbackImage, ImageSampler, TextureFormatPixelInfo},
         Render, RenderApp, RenderSet,
     },
+    transform::TransformSystem,
 };
 
 use crate::{
-    dithering::{add_dither_task, check_dither_compute_tasks, DitheredBuffer, GrassComputeEvent},
-    map::{NormalMap, YMap},
-    prelude::{GrassColor, WarblerHeight},
-    render::{self, cache::UniformBuffer, extract, grass_pipeline::GrassPipeline, prepare, queue},
+    bake::{GrassBake, GrassBakeLoader},
+    chunk_asset::{insert_chunk_assets, GrassChunkAsset, GrassChunkAssetLoader},
+    cutting::{
+        apply_grass_cuts, clear_dirty_blade_states, regrow_blades, reset_blade_states, GrassCut,
+    },
+    displacement::{update_grass_trail, ExtractedDisplacers, GrassDisplacer},
+    dithering::{
+        add_dither_task, check_dither_compute_tasks, DitherTimings, DitheredBuffer,
+        GrassComputeEvent,
+    },
+    map::{ColorMap, NormalMap, YMap},
+    prelude::{GrassAlbedo, GrassBillboard, GrassColor, GrassLighting, GrassLods, WarblerHeight},
+    render::{
+        self,
+        cache::{GpuBladeStates, GrassDrawCalls, InstanceIndexBuffer, UniformBuffer},
+        extract,
+        grass_pipeline::GrassPipeline,
+        prepare, queue,
+    },
+    render_error::{forward_render_errors, render_error_channel, GrassRenderErrorEvent},
+    shader_hooks::{
+        add_default_hooks, update_shader_hooks, GrassShaderHookFlags, GrassShaderHooks,
+    },
+    wind::{update_wind_phase, ExtractedWindZones, GrassWindOverride, WindPhase, WindZone},
     GrassConfiguration, GrassNoiseTexture,
 };
 
@@ -32,6 +53,17 @@ use crate::{
 pub(crate) const GRASS_SHADER_HANDLE: Handle<Shader> =
     Handle::weak_from_u128(2_263_343_952_151_597_127);
 
+/// Raw handles to the importable modules of the grass shader
+const GRASS_TYPES_HANDLE: Handle<Shader> = Handle::weak_from_u128(520_898_673_375_012_619);
+const GRASS_BINDINGS_HANDLE: Handle<Shader> = Handle::weak_from_u128(2_916_752_564_592_747_011);
+const GRASS_UTILS_HANDLE: Handle<Shader> = Handle::weak_from_u128(3_447_751_703_464_063_283);
+const GRASS_WIND_HANDLE: Handle<Shader> = Handle::weak_from_u128(4_202_316_755_018_007_022);
+const GRASS_DISPLACEMENT_HANDLE: Handle<Shader> = Handle::weak_from_u128(9_185_751_165_385_177_193);
+const GRASS_Y_MAP_HANDLE: Handle<Shader> = Handle::weak_from_u128(8_538_853_959_193_855_490);
+const GRASS_NORMAL_HANDLE: Handle<Shader> = Handle::weak_from_u128(8_633_398_719_205_943_545);
+const GRASS_HEIGHT_HANDLE: Handle<Shader> = Handle::weak_from_u128(5_475_527_161_210_554_446);
+const GRASS_COLOR_HANDLE: Handle<Shader> = Handle::weak_from_u128(2_849_161_270_761_015_116);
+
 /// A raw handle to the default mesh used for grass.
 ///
 /// The [`WarblersPlugin`] adds the corresponding mesh to the world.
@@ -59,6 +91,62 @@ impl Plugin for WarblersPlugin {
             "render/assets/grass_shader.wgsl",
             Shader::from_wgsl
         );
+        // Load the modules imported by the grass shader
+        load_internal_asset!(
+            app,
+            GRASS_TYPES_HANDLE,
+            "render/assets/types.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_BINDINGS_HANDLE,
+            "render/assets/bindings.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_UTILS_HANDLE,
+            "render/assets/utils.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_WIND_HANDLE,
+            "render/assets/wind.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_DISPLACEMENT_HANDLE,
+            "render/assets/displacement.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_Y_MAP_HANDLE,
+            "render/assets/y_map.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_NORMAL_HANDLE,
+            "render/assets/normal.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_HEIGHT_HANDLE,
+            "render/assets/height.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            GRASS_COLOR_HANDLE,
+            "render/assets/color.wgsl",
+            Shader::from_wgsl
+        );
+        add_default_hooks(&mut app.world.resource_mut::<Assets<Shader>>());
 
         // Load default grass blade mesh
         let mut meshes = app.world.resource_mut::<Assets<Mesh>>();
@@ -69,34 +157,88 @@ impl Plugin for WarblersPlugin {
         images.insert(DEFAULT_NORMAL_MAP_HANDLE, default_normal_map());
         images.insert(DEFAULT_IMAGE_HANDLE, Image::default());
 
-        app.add_systems(Update, (add_dither_task, check_dither_compute_tasks))
-            .add_event::<GrassComputeEvent>()
-            .init_asset::<DitheredBuffer>()
-            .add_plugins(RenderAssetPlugin::<DitheredBuffer>::default());
+        app.add_systems(
+            Update,
+            (
+                insert_chunk_assets.before(add_dither_task),
+                add_dither_task,
+                check_dither_compute_tasks,
+            ),
+        )
+        .add_systems(First, clear_dirty_blade_states)
+        .add_systems(PostUpdate, (update_wind_phase, update_shader_hooks))
+        .add_systems(
+            Update,
+            (reset_blade_states, apply_grass_cuts, regrow_blades).chain(),
+        )
+        .add_systems(
+            PostUpdate,
+            update_grass_trail.after(TransformSystem::TransformPropagate),
+        )
+        .add_systems(Update, forward_render_errors)
+        .add_event::<GrassComputeEvent>()
+        .add_event::<GrassRenderErrorEvent>()
+        .add_event::<GrassCut>()
+        .init_asset::<DitheredBuffer>()
+        .init_asset::<GrassChunkAsset>()
+        .init_asset_loader::<GrassChunkAssetLoader>()
+        .init_asset::<GrassBake>()
+        .init_asset_loader::<GrassBakeLoader>()
+        .add_plugins(RenderAssetPlugin::<DitheredBuffer>::default());
         // Init resources
         app.init_resource::<GrassConfiguration>()
             .init_resource::<Time>()
             .register_type::<GrassConfiguration>()
-            .init_resource::<GrassNoiseTexture>();
+            .register_type::<GrassDisplacer>()
+            .register_type::<WindZone>()
+            .register_type::<GrassWindOverride>()
+            .register_type::<Handle<GrassChunkAsset>>()
+            .init_resource::<GrassNoiseTexture>()
+            .init_resource::<WindPhase>()
+            .init_resource::<DitherTimings>()
+            .init_resource::<GrassShaderHooks>();
         // Add extraction of the configuration
         app.add_plugins((
             ExtractResourcePlugin::<GrassConfiguration>::default(),
             ExtractResourcePlugin::<GrassNoiseTexture>::default(),
+            ExtractResourcePlugin::<WindPhase>::default(),
+            ExtractResourcePlugin::<GrassShaderHookFlags>::default(),
             ExtractComponentPlugin::<YMap>::default(),
             ExtractComponentPlugin::<NormalMap>::default(),
+            ExtractComponentPlugin::<ColorMap>::default(),
             ExtractComponentPlugin::<WarblerHeight>::default(),
             ExtractComponentPlugin::<GrassColor>::default(),
+            ExtractComponentPlugin::<GrassAlbedo>::default(),
+            ExtractComponentPlugin::<GrassLods>::default(),
+            ExtractComponentPlugin::<GrassBillboard>::default(),
+            ExtractComponentPlugin::<GrassLighting>::default(),
+            ExtractComponentPlugin::<GrassWindOverride>::default(),
         ));
+        // Specialization errors are sent from the render world to the main world
+        let (render_errors, render_error_receiver) = render_error_channel();
+        app.insert_resource(render_error_receiver);
+        // The draw calls are counted in the render world and read in the main world
+        let draw_calls = GrassDrawCalls::default();
+        app.insert_resource(draw_calls.clone());
         // Init render app
         app.sub_app_mut(RenderApp)
+            .insert_resource(render_errors)
+            .insert_resource(draw_calls)
             .add_render_command::<Opaque3d, render::GrassDrawCall>()
             .init_resource::<SpecializedMeshPipelines<GrassPipeline>>()
+            .init_resource::<GpuBladeStates>()
+            .init_resource::<InstanceIndexBuffer>()
+            .init_resource::<ExtractedDisplacers>()
+            .init_resource::<ExtractedWindZones>()
             .add_systems(
                 ExtractSchedule,
                 (
                     extract::extract_grass,
                     extract::extract_aabb,
-                    extract::extract_time,
+                    extract::extract_blade_states,
+                    extract::extract_displacers,
+                    extract::extract_grass_trail,
+                    extract::extract_wind_zones,
                 ),
             )
             .add_systems(
@@ -106,7 +248,7 @@ impl Plugin for WarblersPlugin {
                     prepare::prepare_height_buffer,
                     prepare::prepare_grass_color,
                     prepare::prepare_y_map_buffer,
-                    prepare::prepare_normal_map_buffer,
+                    prepare::prepare_blade_states,
                     prepare::prepare_instance_index
                         .after(batch_and_prepare_render_phase::<Opaque3d, MeshPipeline>),
                 )
@@ -115,6 +257,10 @@ impl Plugin for WarblersPlugin {
             .add_systems(
                 Render,
                 queue::queue_grass_buffers.in_set(RenderSet::QueueMeshes),
+            )
+            .add_systems(
+                Render,
+                GrassDrawCalls::finish_frame.in_set(RenderSet::Cleanup),
             );
     }
 
@@ -164,7 +310,8 @@ fn default_normal_map() -> Image {
 /// Constructs the default mesh of the grass, as shown in the examples
 ///
 /// Can be overridden in the corresponding [`Bundle`] using the grass_mesh [`Component`].
-/// You can take a look at the grass_mesh example in the repository on how this might work
+/// You can take a look at the grass_mesh example in the repository on how this might work.
+/// Other blade meshes can be generated with the [`GrassBladeMeshBuilder`](crate::blade_mesh::GrassBladeMeshBuilder)
 fn default_grass_mesh() -> Mesh {
     let mut grass_mesh = Mesh::new(
         PrimitiveTopology::TriangleList,
@@ -0,0 +1,157 @@
+//! Contains components to change the wind locally.
+//!
+//! A [`WindZone`] changes the wind around an entity, a [`GrassWindOverride`] changes the wind of a single chunk.
+//! Both are combined with the global wind defined in the [`GrassConfiguration`](crate::GrassConfiguration)
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, extract_resource::ExtractResource},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{GrassConfiguration, GrassNoiseTexture};
+
+/// The maximum amount of [`WindZone`]s that affect the grass at the same time.
+///
+/// If more zones exist, the ones closest to the camera are used
+pub const MAX_WIND_ZONES: usize = 8;
+
+/// Changes the wind in a sphere around the entity.
+///
+/// The position of the zone is taken from its [`GlobalTransform`].
+/// The effect of the zone fades out towards the `radius`
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct WindZone {
+    /// How the zone changes the wind
+    pub kind: WindZoneKind,
+    /// The radius of the zone
+    pub radius: f32,
+    /// How fast the effect fades out towards the radius.
+    ///
+    /// 0 means the zone has the full effect everywhere inside of the radius,
+    /// 1 means a linear falloff
+    pub falloff: f32,
+}
+impl Default for WindZone {
+    fn default() -> Self {
+        WindZone {
+            kind: WindZoneKind::Directional(Vec2::ZERO),
+            radius: 10.,
+            falloff: 1.,
+        }
+    }
+}
+/// Defines how a [`WindZone`] changes the wind
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum WindZoneKind {
+    /// Replaces the global wind inside of the zone.
+    ///
+    /// Use `Vec2::ZERO` for a calm zone.
+    /// The value has the same meaning as [`GrassConfiguration::wind`](crate::GrassConfiguration::wind)
+    Directional(Vec2),
+    /// Pushes the grass away from the center of the zone with the given strength, like the downdraft of a helicopter.
+    ///
+    /// A negative strength pulls the grass towards the center
+    Radial(f32),
+}
+/// Replaces [`GrassConfiguration::wind`](crate::GrassConfiguration::wind) for a single chunk.
+///
+/// The wind noise still scrolls in the direction of the global wind,
+/// so that neighbouring chunks stay in sync
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct GrassWindOverride(pub Vec2);
+
+impl ExtractComponent for GrassWindOverride {
+    type QueryData = &'static Self;
+
+    type QueryFilter = ();
+
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        Some(*item)
+    }
+}
+
+/// A [resource](bevy::prelude::Resource) storing the current state of the wind animation.
+///
+/// The phase accumulates the elapsed [`Time`] scaled by the wind speed every frame,
+/// so the animation never jumps, even if the speed changes or the app runs for a long time.
+/// Since the virtual time is used, pausing [`Time<Virtual>`] also freezes the wind.
+///
+/// Inserted and updated by the [`WarblersPlugin`](crate::warblers_plugin::WarblersPlugin)
+#[derive(Resource, Clone, Copy, Debug, Default, ExtractResource)]
+pub struct WindPhase {
+    /// The offset of the wind noise in pixels of the [`GrassNoiseTexture`].
+    ///
+    /// Wraps at the size of the texture, which is seamless since the texture is tileable
+    pub noise_offset: Vec2,
+    /// The phase of the gusts in the range `[0, 1)`
+    pub gust_phase: f32,
+}
+pub(crate) fn update_wind_phase(
+    time: Res<Time>,
+    config: Res<GrassConfiguration>,
+    noise: Res<GrassNoiseTexture>,
+    images: Res<Assets<Image>>,
+    mut phase: ResMut<WindPhase>,
+) {
+    let delta = time.delta_seconds();
+    if delta == 0. {
+        return;
+    }
+    let period = images
+        .get(&noise.0)
+        .map_or(Vec2::ONE, |image| image.size().as_vec2());
+    let direction = config.wind.normalize_or_zero();
+    let offset = phase.noise_offset + direction * config.wind_speed * delta;
+    phase.noise_offset = offset.rem_euclid(period);
+    phase.gust_phase = (phase.gust_phase + config.gust_frequency * delta).rem_euclid(1.);
+}
+
+/// A [`WindZone`] as seen by the shader
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ShaderWindZone {
+    /// xyz: the world position, w: the radius
+    position_radius: Vec4,
+    /// x: the kind (0: directional, 1: radial), y: the falloff,
+    /// zw: the wind for directional zones, z: the strength for radial zones
+    params: Vec4,
+}
+impl ShaderWindZone {
+    pub fn new(zone: &WindZone, position: Vec3) -> Self {
+        let params = match zone.kind {
+            WindZoneKind::Directional(wind) => Vec4::new(0., zone.falloff, wind.x, wind.y),
+            WindZoneKind::Radial(strength) => Vec4::new(1., zone.falloff, strength, 0.),
+        };
+        ShaderWindZone {
+            position_radius: position.extend(zone.radius),
+            params,
+        }
+    }
+}
+/// All wind zones affecting the grass in the current frame
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ShaderWindZones {
+    count: u32,
+    /// Wasm requires shader uniforms to be aligned to 16 bytes
+    _wasm_padding: [u32; 3],
+    zones: [ShaderWindZone; MAX_WIND_ZONES],
+}
+impl ShaderWindZones {
+    pub fn new(zones: &[ShaderWindZone]) -> Self {
+        let count = zones.len().min(MAX_WIND_ZONES);
+        let mut shader_zones = [ShaderWindZone::default(); MAX_WIND_ZONES];
+        shader_zones[..count].copy_from_slice(&zones[..count]);
+        ShaderWindZones {
+            count: count as u32,
+            _wasm_padding: [0; 3],
+            zones: shader_zones,
+        }
+    }
+}
+/// The wind zones of the current frame in the render world
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedWindZones(pub Vec<ShaderWindZone>);